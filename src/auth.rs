@@ -0,0 +1,778 @@
+//! Bearer-token authentication and per-identity authorization.
+//!
+//! The policy file maps each identity to a token and to the tools, clients, pools, jobs and
+//! storages it may see, as glob patterns (`*` and `?`). Omitted lists are unrestricted,
+//! except that a scoped identity only names the clients and storages it lists, and only
+//! names volumes through its pools:
+//!
+//! ```json
+//! {
+//!   "identities": {
+//!     "web-team": {
+//!       "token": "…",
+//!       "tools": ["list_*", "get_job_*"],
+//!       "clients": ["web*"]
+//!     }
+//!   }
+//! }
+//! ```
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::bareos::BareosClient;
+use crate::tools::ToolOutput;
+use crate::validate::Arguments;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    identities: BTreeMap<String, IdentityConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IdentityConfig {
+    token: String,
+    #[serde(default)]
+    tools: Option<Vec<String>>,
+    #[serde(default)]
+    clients: Option<Vec<String>>,
+    #[serde(default)]
+    pools: Option<Vec<String>>,
+    #[serde(default)]
+    jobs: Option<Vec<String>>,
+    #[serde(default)]
+    storages: Option<Vec<String>>,
+}
+
+/// A caller and what it is allowed to see. `None` lists are unrestricted.
+#[derive(Debug)]
+pub struct Identity {
+    pub name: String,
+    tools: Option<Vec<String>>,
+    clients: Option<Vec<String>>,
+    pools: Option<Vec<String>>,
+    jobs: Option<Vec<String>>,
+    storages: Option<Vec<String>>,
+}
+
+/// Maps bearer tokens to identities.
+pub struct Policy {
+    identities: Vec<(String, Arc<Identity>)>,
+}
+
+/// A tool call the caller's identity is not allowed to make.
+#[derive(Debug)]
+pub struct Forbidden {
+    pub identity: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Forbidden {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Identity '{}' is not allowed to {}",
+            self.identity, self.reason
+        )
+    }
+}
+
+impl std::error::Error for Forbidden {}
+
+impl Policy {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read auth policy {}", path.display()))?;
+        let file: PolicyFile = serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid auth policy {}", path.display()))?;
+
+        let mut identities = Vec::new();
+        for (name, config) in file.identities {
+            if config.token.len() < 16 {
+                anyhow::bail!(
+                    "Token of identity '{}' is too short; use at least 16 characters",
+                    name
+                );
+            }
+            let identity = Identity {
+                name,
+                tools: config.tools,
+                clients: config.clients,
+                pools: config.pools,
+                jobs: config.jobs,
+                storages: config.storages,
+            };
+            identities.push((config.token, Arc::new(identity)));
+        }
+        Ok(Self { identities })
+    }
+
+    /// Resolves the identity for an `Authorization: Bearer <token>` header value.
+    pub fn authenticate(&self, authorization: &str) -> Option<Arc<Identity>> {
+        let token = authorization.strip_prefix("Bearer ")?.trim();
+
+        // Compare against every token so timing does not reveal which one nearly matched
+        let mut found = None;
+        for (candidate, identity) in &self.identities {
+            if constant_time_eq(candidate.as_bytes(), token.as_bytes()) {
+                found = Some(Arc::clone(identity));
+            }
+        }
+        found
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Matches `text` against a pattern where `*` matches any run of characters and `?` one.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// A missing value only passes an unrestricted list
+fn allowed(patterns: &Option<Vec<String>>, value: Option<&str>) -> bool {
+    match (patterns, value) {
+        (None, _) => true,
+        (Some(patterns), Some(value)) => patterns.iter().any(|pattern| glob_match(pattern, value)),
+        (Some(_), None) => false,
+    }
+}
+
+impl Identity {
+    /// The identity of a local caller, e.g. the user who started the stdio server.
+    pub fn unrestricted(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            tools: None,
+            clients: None,
+            pools: None,
+            jobs: None,
+            storages: None,
+        }
+    }
+
+    pub fn allows_tool(&self, tool: &str) -> bool {
+        allowed(&self.tools, Some(tool))
+    }
+
+    pub fn allows_client(&self, client: Option<&str>) -> bool {
+        allowed(&self.clients, client)
+    }
+
+    /// Whether a call may name `client`. A client's files span all its jobs and pools, so a
+    /// scope on pools, jobs or storages does not extend to clients the identity does not list.
+    pub fn reaches_client(&self, client: Option<&str>) -> bool {
+        match &self.clients {
+            None => !self.is_scoped(),
+            clients => allowed(clients, client),
+        }
+    }
+
+    pub fn allows_pool(&self, pool: Option<&str>) -> bool {
+        allowed(&self.pools, pool)
+    }
+
+    pub fn allows_job(&self, job: Option<&str>) -> bool {
+        allowed(&self.jobs, job)
+    }
+
+    /// Whether a call may name a volume of `pool`. Volumes hold the jobs of many clients, so
+    /// only a pool scope reaches them.
+    pub fn allows_volume(&self, pool: Option<&str>) -> bool {
+        match &self.pools {
+            None => !self.is_scoped(),
+            pools => allowed(pools, pool),
+        }
+    }
+
+    /// A storage serves every client, so a scope on clients, pools or jobs does not extend
+    /// to storages the identity does not list.
+    pub fn allows_storage(&self, storage: Option<&str>) -> bool {
+        match &self.storages {
+            None => !self.is_scoped(),
+            storages => allowed(storages, storage),
+        }
+    }
+
+    /// Whether catalog records have to be filtered for this identity.
+    pub fn is_scoped(&self) -> bool {
+        self.clients.is_some()
+            || self.pools.is_some()
+            || self.jobs.is_some()
+            || self.storages.is_some()
+    }
+
+    fn forbidden(&self, reason: impl Into<String>) -> Forbidden {
+        Forbidden {
+            identity: self.name.clone(),
+            reason: reason.into(),
+        }
+    }
+}
+
+// Tools returning configuration text that names resources of every client
+const UNFILTERED_TOOLS: &[&str] = &["show_jobdefs", "show_schedule", "list_filesets"];
+
+/// Checks a tool call against `identity` before it is run.
+///
+/// Arguments naming a client, pool or storage are checked directly; job names are resolved
+/// through the job resource's defaults, job IDs and volumes through the catalog.
+pub async fn authorize(
+    client: &BareosClient,
+    identity: &Identity,
+    tool: &str,
+    arguments: &Value,
+) -> Result<()> {
+    if !identity.allows_tool(tool) {
+        return Err(identity.forbidden(format!("use tool '{}'", tool)).into());
+    }
+    if !identity.is_scoped() {
+        return Ok(());
+    }
+
+    let argument = |name: &str| arguments.get(name).and_then(Value::as_str);
+
+    for key in ["client", "restore_client"] {
+        if let Some(name) = argument(key) {
+            if !identity.reaches_client(Some(name)) {
+                return Err(identity
+                    .forbidden(format!("access client '{}'", name))
+                    .into());
+            }
+        }
+    }
+    if let Some(name) = argument("pool") {
+        if !identity.allows_pool(Some(name)) {
+            return Err(identity.forbidden(format!("access pool '{}'", name)).into());
+        }
+    }
+    if let Some(name) = argument("storage") {
+        if !identity.allows_storage(Some(name)) {
+            return Err(identity
+                .forbidden(format!("access storage '{}'", name))
+                .into());
+        }
+    }
+
+    // Configuration text cannot be filtered, so resources that are not tied to a client,
+    // pool or job, and listing all resources of a scoped type, are refused
+    if UNFILTERED_TOOLS.contains(&tool) {
+        return Err(identity
+            .forbidden(format!("use tool '{}' with a scoped identity", tool))
+            .into());
+    }
+    let mut job_name = None;
+    if tool == "show_resource" {
+        let resource_type = argument("resource_type").unwrap_or_default();
+        let name = argument("name");
+        let allowed = match resource_type {
+            "client" => identity.reaches_client(name),
+            "pool" => identity.allows_pool(name),
+            "storage" => identity.allows_storage(name),
+            "job" => {
+                job_name = name;
+                identity.allows_job(name)
+            }
+            _ => false,
+        };
+        if !allowed {
+            return Err(identity
+                .forbidden(format!("show {} '{}'", resource_type, name.unwrap_or("*")))
+                .into());
+        }
+    }
+
+    // A job name is checked against the client and pool its resource runs with, unless the
+    // call overrides them (those arguments were checked above)
+    for name in ["job", "job_name"]
+        .into_iter()
+        .filter_map(argument)
+        .chain(job_name)
+    {
+        let mut allowed = identity.allows_job(Some(name));
+        if allowed && (identity.clients.is_some() || identity.pools.is_some()) {
+            let defaults = client.job_defaults(name).await?;
+            let default = |key: &str| defaults.get(key).map(String::as_str);
+            allowed = identity.allows_client(argument("client").or(default("client")))
+                && identity.allows_pool(argument("pool").or(default("pool")));
+        }
+        if !allowed {
+            return Err(identity.forbidden(format!("access job '{}'", name)).into());
+        }
+    }
+
+    // A count cannot be filtered after the fact
+    if tool == "list_jobs" && arguments.get("count") == Some(&Value::Bool(true)) {
+        return Err(identity
+            .forbidden("count jobs across clients; list them instead")
+            .into());
+    }
+
+    let job_id = match arguments.get("job_id") {
+        Some(Value::Number(number)) => number.as_u64(),
+        Some(Value::String(text)) => text.parse().ok(),
+        _ => None,
+    };
+    // The files of a BVFS restore selection come from its `jobids`, whichever client they
+    // are restored to
+    let selected = Arguments::new(arguments)?
+        .optional_ids("jobids")?
+        .unwrap_or_default();
+    // The Director looks `fileids` up without checking that they belong to those jobs, so a
+    // scoped identity can only select whole directories (`dirids`, which it resolves within
+    // the jobs)
+    if identity.clients.is_some() || identity.pools.is_some() || identity.jobs.is_some() {
+        if let Some(Value::Array(file_ids)) = arguments.get("fileids") {
+            if !file_ids.is_empty() {
+                return Err(identity
+                    .forbidden("restore by fileids with a scoped identity; use dirids or files")
+                    .into());
+            }
+        }
+    }
+    for job_id in job_id.into_iter().chain(selected) {
+        for job in client.get_job_status(job_id).await? {
+            if !identity.allows_job(Some(&job.name))
+                || !identity.allows_client(job.client.as_deref())
+                || !identity.allows_pool(job.pool.as_deref())
+            {
+                return Err(identity.forbidden(format!("access job {}", job_id)).into());
+            }
+        }
+    }
+
+    if let Some(name) = argument("volume") {
+        let pool = match identity.pools {
+            Some(_) => client
+                .list_volumes(None)
+                .await?
+                .into_iter()
+                .find(|volume| volume.name == name)
+                .and_then(|volume| volume.pool),
+            None => None,
+        };
+        if !identity.allows_volume(pool.as_deref()) {
+            return Err(identity
+                .forbidden(format!("access volume '{}'", name))
+                .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Drops catalog records `identity` may not see from a tool's structured output.
+pub fn filter_output(identity: &Identity, output: ToolOutput) -> ToolOutput {
+    let ToolOutput::Structured(mut content) = output else {
+        return output;
+    };
+    if !identity.is_scoped() {
+        return ToolOutput::Structured(content);
+    }
+
+    let field =
+        |record: &Value, key: &str| record.get(key).and_then(Value::as_str).map(str::to_string);
+    if let Some(Value::Array(jobs)) = content.get_mut("jobs") {
+        jobs.retain(|job| {
+            identity.allows_job(field(job, "name").as_deref())
+                && identity.allows_client(field(job, "client").as_deref())
+                && identity.allows_pool(field(job, "poolname").as_deref())
+        });
+    }
+    // Jobs in `status` reports only carry their name, so client and pool scopes hide them
+    for key in ["running", "scheduled", "terminated"] {
+        if let Some(Value::Array(jobs)) = content.get_mut(key) {
+            jobs.retain(|job| {
+                identity.allows_job(field(job, "name").as_deref())
+                    && identity.allows_client(field(job, "client").as_deref())
+                    && identity.allows_pool(field(job, "pool").as_deref())
+            });
+        }
+    }
+    // A mounted volume reveals its pool; idle devices reveal nothing
+    if let Some(Value::Array(devices)) = content.get_mut("devices") {
+        devices.retain(|device| {
+            device.get("volume").is_none() || identity.allows_pool(field(device, "pool").as_deref())
+        });
+    }
+    if let Some(Value::Array(clients)) = content.get_mut("clients") {
+        clients.retain(|client| identity.allows_client(field(client, "name").as_deref()));
+    }
+    if let Some(Value::Array(pools)) = content.get_mut("pools") {
+        pools.retain(|pool| identity.allows_pool(field(pool, "name").as_deref()));
+    }
+    if let Some(Value::Array(volumes)) = content.get_mut("volumes") {
+        volumes.retain(|volume| identity.allows_pool(field(volume, "pool").as_deref()));
+    }
+    ToolOutput::Structured(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBconsole;
+    use serde_json::json;
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_match("web*", "web01-fd"));
+        assert!(glob_match("*-fd", "web01-fd"));
+        assert!(glob_match("web??-fd", "web01-fd"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("web*", "db01-fd"));
+        assert!(!glob_match("web?", "web"));
+        assert!(!glob_match("a*b", "axxbc"));
+    }
+
+    #[test]
+    fn missing_values_only_pass_unrestricted_lists() {
+        let identity = Identity {
+            clients: Some(vec!["web*".to_string()]),
+            ..Identity::unrestricted("web-team")
+        };
+        assert!(identity.allows_client(Some("web01-fd")));
+        assert!(!identity.allows_client(None));
+        assert!(identity.allows_pool(None));
+    }
+
+    #[test]
+    fn authenticates_bearer_tokens() {
+        let policy = Policy {
+            identities: vec![(
+                "0123456789abcdef".to_string(),
+                Arc::new(Identity::unrestricted("ops")),
+            )],
+        };
+        let identity = policy.authenticate("Bearer 0123456789abcdef");
+        assert_eq!(
+            identity.map(|identity| identity.name.clone()),
+            Some("ops".to_string())
+        );
+        assert!(policy.authenticate("Bearer 0123456789abcdeX").is_none());
+        assert!(policy.authenticate("0123456789abcdef").is_none());
+    }
+
+    #[test]
+    fn filters_records_outside_scope() {
+        let identity = Identity {
+            clients: Some(vec!["web*".to_string()]),
+            ..Identity::unrestricted("web-team")
+        };
+        let output = ToolOutput::Structured(json!({
+            "jobs": [
+                {"jobid": 1, "name": "backup-web", "client": "web01-fd"},
+                {"jobid": 2, "name": "backup-db", "client": "db01-fd"},
+                {"jobid": 3, "name": "admin"}
+            ],
+            "clients": [{"name": "web01-fd"}, {"name": "db01-fd"}]
+        }));
+        let ToolOutput::Structured(content) = filter_output(&identity, output) else {
+            panic!("expected structured output");
+        };
+        assert_eq!(
+            content["jobs"],
+            json!([{"jobid": 1, "name": "backup-web", "client": "web01-fd"}])
+        );
+        assert_eq!(content["clients"], json!([{"name": "web01-fd"}]));
+    }
+
+    const DEFAULTS: &str = r#"
+  ".defaults job=backup-web") printf 'job=backup-web\nclient=web01-fd\npool=Full\n' ;;
+  ".defaults job=backup-db") printf 'job=backup-db\nclient=db01-fd\npool=Full\n' ;;"#;
+
+    fn web_team() -> Identity {
+        Identity {
+            clients: Some(vec!["web*".to_string()]),
+            ..Identity::unrestricted("web-team")
+        }
+    }
+
+    #[tokio::test]
+    async fn checks_the_client_of_named_jobs() {
+        let fake = FakeBconsole::new(DEFAULTS);
+        let client = fake.client();
+        let identity = web_team();
+        for (tool, key) in [
+            ("run_job", "job"),
+            ("enable_job", "job"),
+            ("disable_job", "job"),
+            ("show_job", "job_name"),
+        ] {
+            let allowed = json!({ key: "backup-web" });
+            assert!(authorize(&client, &identity, tool, &allowed).await.is_ok());
+            let other = json!({ key: "backup-db" });
+            let error = authorize(&client, &identity, tool, &other)
+                .await
+                .unwrap_err();
+            assert!(error.downcast_ref::<Forbidden>().is_some(), "{}", tool);
+        }
+
+        // Running another client's job on an allowed client is up to the client scope
+        let overridden = json!({ "job": "backup-db", "client": "web02-fd" });
+        assert!(authorize(&client, &identity, "run_job", &overridden)
+            .await
+            .is_ok());
+        let resource = json!({ "resource_type": "job", "name": "backup-db" });
+        assert!(authorize(&client, &identity, "show_resource", &resource)
+            .await
+            .is_err());
+        let unknown = json!({ "job": "no-such-job" });
+        assert!(authorize(&client, &identity, "run_job", &unknown)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn job_scopes_do_not_look_up_defaults() {
+        let fake = FakeBconsole::new(DEFAULTS);
+        let identity = Identity {
+            jobs: Some(vec!["backup-*".to_string()]),
+            ..Identity::unrestricted("backups")
+        };
+        let arguments = json!({ "job": "backup-db" });
+        assert!(authorize(&fake.client(), &identity, "run_job", &arguments)
+            .await
+            .is_ok());
+        assert!(fake.commands().is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuses_unfiltered_configuration_to_scoped_identities() {
+        let fake = FakeBconsole::new(DEFAULTS);
+        let client = fake.client();
+        let identity = web_team();
+        for (tool, arguments) in [
+            ("show_jobdefs", json!({ "jobdefs_name": "DefaultJob" })),
+            ("show_schedule", json!({ "schedule_name": "WeeklyCycle" })),
+            ("list_filesets", json!({})),
+        ] {
+            let error = authorize(&client, &identity, tool, &arguments)
+                .await
+                .unwrap_err();
+            assert!(error.downcast_ref::<Forbidden>().is_some(), "{}", tool);
+            assert!(
+                authorize(&client, &Identity::unrestricted("ops"), tool, &arguments)
+                    .await
+                    .is_ok()
+            );
+        }
+        for resource_type in [
+            "director", "jobdefs", "schedule", "fileset", "storage", "messages", "catalog",
+            "console", "profile", "counter", "user",
+        ] {
+            let arguments = json!({ "resource_type": resource_type, "name": "x" });
+            assert!(
+                authorize(&client, &identity, "show_resource", &arguments)
+                    .await
+                    .is_err(),
+                "{}",
+                resource_type
+            );
+        }
+        let allowed = json!({ "resource_type": "client", "name": "web01-fd" });
+        assert!(authorize(&client, &identity, "show_resource", &allowed)
+            .await
+            .is_ok());
+        let all = json!({ "resource_type": "client" });
+        assert!(authorize(&client, &identity, "show_resource", &all)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn checks_the_jobs_of_restore_selections() {
+        let fake = FakeBconsole::new(
+            r#"
+  "list jobid=3") echo '{"result":{"jobs":[{"jobid":3,"name":"backup-web","client":"web01-fd","type":"B","level":"F","jobstatus":"T"}]}}' ;;
+  "list jobid=4") echo '{"result":{"jobs":[{"jobid":4,"name":"backup-db","client":"db01-fd","type":"B","level":"F","jobstatus":"T"}]}}' ;;"#,
+        );
+        let client = fake.client();
+        let identity = web_team();
+        let own = json!({ "client": "web01-fd", "jobids": [3], "dirids": [7] });
+        assert!(authorize(&client, &identity, "run_restore", &own)
+            .await
+            .is_ok());
+        for arguments in [
+            json!({ "client": "web01-fd", "jobids": [3, "4"], "dirids": [7] }),
+            json!({ "client": "web01-fd", "jobids": [3], "fileids": [10] }),
+        ] {
+            let error = authorize(&client, &identity, "run_restore", &arguments)
+                .await
+                .unwrap_err();
+            assert!(error.downcast_ref::<Forbidden>().is_some(), "{}", arguments);
+        }
+
+        let ops = Identity::unrestricted("ops");
+        let other = json!({ "client": "web01-fd", "jobids": [4], "fileids": [10] });
+        assert!(authorize(&client, &ops, "run_restore", &other)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn only_lets_scoped_identities_use_listed_storages() {
+        let fake = FakeBconsole::new(DEFAULTS);
+        let client = fake.client();
+        for (tool, arguments) in [
+            ("status_storage", json!({ "storage": "File" })),
+            ("mount", json!({ "storage": "Tape" })),
+            ("run_job", json!({ "job": "backup-web", "storage": "Tape" })),
+            (
+                "show_resource",
+                json!({ "resource_type": "storage", "name": "Tape" }),
+            ),
+        ] {
+            let error = authorize(&client, &web_team(), tool, &arguments)
+                .await
+                .unwrap_err();
+            assert!(error.downcast_ref::<Forbidden>().is_some(), "{}", tool);
+
+            let storages = Identity {
+                storages: Some(vec!["File".to_string(), "Tape".to_string()]),
+                ..web_team()
+            };
+            assert!(authorize(&client, &storages, tool, &arguments)
+                .await
+                .is_ok());
+            let other = Identity {
+                storages: Some(vec!["Disk*".to_string()]),
+                ..Identity::unrestricted("disk-team")
+            };
+            assert!(authorize(&client, &other, tool, &arguments).await.is_err());
+            assert!(
+                authorize(&client, &Identity::unrestricted("ops"), tool, &arguments)
+                    .await
+                    .is_ok()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn only_lets_scoped_identities_name_listed_clients() {
+        let fake = FakeBconsole::new(DEFAULTS);
+        let client = fake.client();
+        let identity = Identity {
+            pools: Some(vec!["Full".to_string()]),
+            ..Identity::unrestricted("full-pool")
+        };
+        for (tool, arguments) in [
+            ("browse_backup", json!({ "client": "db01-fd" })),
+            (
+                "list_file_versions",
+                json!({ "client": "db01-fd", "path": "/etc/hosts" }),
+            ),
+            (
+                "plan_restore",
+                json!({ "client": "db01-fd", "paths": ["/etc/"] }),
+            ),
+            ("status_client", json!({ "client": "db01-fd" })),
+            (
+                "run_restore",
+                json!({ "client": "db01-fd", "files": ["/etc/hosts"] }),
+            ),
+            (
+                "run_restore",
+                json!({ "client": "web01-fd", "restore_client": "db01-fd", "files": ["/etc/hosts"] }),
+            ),
+            (
+                "show_resource",
+                json!({ "resource_type": "client", "name": "db01-fd" }),
+            ),
+        ] {
+            let error = authorize(&client, &identity, tool, &arguments)
+                .await
+                .unwrap_err();
+            assert!(error.downcast_ref::<Forbidden>().is_some(), "{}", tool);
+        }
+
+        // Their jobs still run on the client the job resource names
+        let job = json!({ "job": "backup-db" });
+        assert!(authorize(&client, &identity, "run_job", &job).await.is_ok());
+        let browse = json!({ "client": "web01-fd" });
+        assert!(authorize(&client, &web_team(), "browse_backup", &browse)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn only_lets_pool_scopes_name_volumes() {
+        let fake = FakeBconsole::new(
+            r#"
+  "list volumes") echo '{"result":{"volumes":{"Full":[{"mediaid":1,"volumename":"Full-0001","volstatus":"Full"}]}}}' ;;"#,
+        );
+        let client = fake.client();
+        let arguments = json!({ "volume": "Full-0001", "status": "Recycle" });
+        for identity in [
+            web_team(),
+            Identity {
+                jobs: Some(vec!["backup-*".to_string()]),
+                ..Identity::unrestricted("backups")
+            },
+        ] {
+            let error = authorize(&client, &identity, "update_volume_status", &arguments)
+                .await
+                .unwrap_err();
+            assert!(
+                error.downcast_ref::<Forbidden>().is_some(),
+                "{}",
+                identity.name
+            );
+        }
+        assert!(fake.commands().is_empty());
+
+        let full = Identity {
+            pools: Some(vec!["Full".to_string()]),
+            ..Identity::unrestricted("full-pool")
+        };
+        assert!(
+            authorize(&client, &full, "update_volume_status", &arguments)
+                .await
+                .is_ok()
+        );
+        let incremental = Identity {
+            pools: Some(vec!["Incremental".to_string()]),
+            ..Identity::unrestricted("incremental-pool")
+        };
+        assert!(
+            authorize(&client, &incremental, "update_volume_status", &arguments)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -1,7 +1,34 @@
 use anyhow::{Context, Result};
-use tokio::io::AsyncWriteExt;
-use tokio::process::Command;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use crate::bvfs::{self, BvfsEntry};
+use crate::director::Director;
+use crate::model::{self, Client, FileSet, Job, JobCount, JobLogEntry, JobMedia, Pool, Volume};
+use crate::policy;
+use crate::redact::Redactor;
+use crate::session::{Bconsole, OutputMode, SessionPool};
+use crate::status::{DaemonStatus, DirectorStatus};
+use crate::validate::quote;
+
+pub use crate::session::Timeout;
+
+// Enough that one slow command (e.g. `status client` of an unreachable client) does not hold
+// up the rest, while staying well below the Director's default MaximumConsoleConnections of 20
+const DEFAULT_SESSION_POOL_SIZE: usize = 4;
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(300);
+
+// How long `.defaults job=` answers are reused; authorizing a scoped identity asks for them
+// for every job named, and `resources/list` names every job
+const JOB_DEFAULTS_TTL: Duration = Duration::from_secs(60);
+
+// When the defaults were fetched, and the defaults
+type CachedDefaults = (Instant, Arc<HashMap<String, String>>);
+
+#[derive(Default)]
 pub struct JobListParams {
     pub job: Option<String>,
     pub client: Option<String>,
@@ -14,148 +41,654 @@ pub struct JobListParams {
     pub hours: Option<u32>,
     pub last: bool,
     pub count: bool,
+    /// `llist`: full catalog records, including JobTDate, ClientId and FileSetId.
+    pub long: bool,
+}
+
+enum Backend {
+    Bconsole(SessionPool<Bconsole>),
+    Director(SessionPool<Director>),
 }
 
 pub struct BareosClient {
-    bconsole_path: String,
+    backend: Backend,
+    write_mode: bool,
+    redactor: Redactor,
+    // Defaults of the jobs asked for lately, by job name
+    job_defaults: Mutex<HashMap<String, CachedDefaults>>,
 }
 
 impl Default for BareosClient {
     fn default() -> Self {
-        Self {
-            bconsole_path: "bconsole".to_string(),
-        }
+        Self::bconsole("bconsole", DEFAULT_SESSION_POOL_SIZE)
     }
 }
 
 impl BareosClient {
-    pub fn new() -> Self {
-        let bconsole_path =
-            std::env::var("BAREOS_BCONSOLE_PATH").unwrap_or_else(|_| "bconsole".to_string());
-        Self { bconsole_path }
-    }
+    /// Configures the client from the `BAREOS_*` environment variables.
+    pub fn new() -> Result<Self> {
+        let pool_size = std::env::var("BAREOS_SESSION_POOL_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_SESSION_POOL_SIZE);
 
-    async fn execute_command(&self, command: &str) -> Result<String> {
-        let mut child = Command::new(&self.bconsole_path)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .context("Failed to spawn bconsole")?;
-
-        let mut stdin = child.stdin.take().context("Failed to open stdin")?;
-
-        // Write commands to bconsole
-        stdin.write_all(format!("{}\n", command).as_bytes()).await?;
-        stdin.write_all(b"quit\n").await?;
-        drop(stdin);
-
-        let output = child
-            .wait_with_output()
-            .await
-            .context("Failed to read bconsole output")?;
+        let write_mode = std::env::var("BAREOS_WRITE_MODE")
+            .map(|value| value == "true" || value == "1")
+            .unwrap_or(false);
 
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        let command_timeout = std::env::var("BAREOS_COMMAND_TIMEOUT_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_COMMAND_TIMEOUT);
 
-        // bconsole writes connection info to stderr and may return non-zero exit codes
-        // Only fail if we have no stdout and stderr contains actual error messages
-        if stdout.is_empty() && !output.status.success() {
-            anyhow::bail!("bconsole command failed: {}", stderr);
-        }
+        let client = match std::env::var("BAREOS_BACKEND").as_deref() {
+            Ok("director") => Self::director(Director::from_env()?, pool_size),
+            Ok("bconsole") | Err(_) => {
+                let bconsole_path = std::env::var("BAREOS_BCONSOLE_PATH")
+                    .unwrap_or_else(|_| "bconsole".to_string());
+                Self::bconsole(bconsole_path, pool_size)
+            }
+            Ok(other) => anyhow::bail!("Unknown BAREOS_BACKEND: {}", other),
+        };
 
-        Ok(stdout)
+        Ok(client
+            .with_write_mode(write_mode)
+            .with_command_timeout(command_timeout)
+            .with_redactor(Redactor::from_env()?))
     }
 
-    pub async fn list_jobs(&self, params: JobListParams) -> Result<String> {
-        let mut cmd = "list jobs".to_string();
-
-        // Pass all parameters to bconsole - it handles precedence and filtering
-        if let Some(job) = params.job {
-            cmd.push_str(&format!(" job={}", job));
-        }
-        if let Some(client) = params.client {
-            cmd.push_str(&format!(" client={}", client));
-        }
-        if let Some(jobstatus) = params.jobstatus {
-            cmd.push_str(&format!(" jobstatus={}", jobstatus));
-        }
-        if let Some(jobtype) = params.jobtype {
-            cmd.push_str(&format!(" jobtype={}", jobtype));
+    /// Talks to the Director through `pool_size` long-lived bconsole processes.
+    pub fn bconsole(bconsole_path: impl Into<String>, pool_size: usize) -> Self {
+        let bconsole = Bconsole {
+            path: bconsole_path.into(),
+        };
+        Self {
+            backend: Backend::Bconsole(SessionPool::new(
+                bconsole,
+                pool_size,
+                DEFAULT_COMMAND_TIMEOUT,
+            )),
+            write_mode: false,
+            redactor: Redactor::default(),
+            job_defaults: Mutex::new(HashMap::new()),
         }
-        if let Some(joblevel) = params.joblevel {
-            cmd.push_str(&format!(" joblevel={}", joblevel));
+    }
+
+    /// Connects to the Director directly over the console protocol.
+    pub fn director(director: Director, pool_size: usize) -> Self {
+        Self {
+            backend: Backend::Director(SessionPool::new(
+                director,
+                pool_size,
+                DEFAULT_COMMAND_TIMEOUT,
+            )),
+            write_mode: false,
+            redactor: Redactor::default(),
+            job_defaults: Mutex::new(HashMap::new()),
         }
-        if let Some(volume) = params.volume {
-            cmd.push_str(&format!(" volume={}", volume));
+    }
+
+    /// Allows `execute_mutating`; without it the client only ever sends read-only commands.
+    pub fn with_write_mode(mut self, enabled: bool) -> Self {
+        self.write_mode = enabled;
+        self
+    }
+
+    pub fn write_mode(&self) -> bool {
+        self.write_mode
+    }
+
+    /// Replaces the built-in secret masking, e.g. to add site-specific patterns.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Limits how long a single command may run before its session is killed.
+    pub fn with_command_timeout(mut self, timeout: Duration) -> Self {
+        match &mut self.backend {
+            Backend::Bconsole(sessions) => sessions.set_timeout(timeout),
+            Backend::Director(sessions) => sessions.set_timeout(timeout),
         }
-        if let Some(pool) = params.pool {
-            cmd.push_str(&format!(" pool={}", pool));
+        self
+    }
+
+    async fn dispatch(&self, command: &str, mode: OutputMode) -> Result<String> {
+        match &self.backend {
+            Backend::Bconsole(sessions) => sessions.execute(command, mode).await,
+            Backend::Director(sessions) => sessions.execute(command, mode).await,
         }
-        if let Some(days) = params.days {
-            cmd.push_str(&format!(" days={}", days));
+    }
+
+    async fn execute(&self, command: &str, mode: OutputMode) -> Result<String> {
+        policy::check_read_only(command)?;
+        self.dispatch(command, mode).await
+    }
+
+    /// Sends a command that changes Director state; requires write mode.
+    pub async fn execute_mutating(&self, command: &str) -> Result<String> {
+        if !self.write_mode {
+            anyhow::bail!(
+                "Write operations are disabled; set BAREOS_WRITE_MODE=true to enable them"
+            );
         }
-        if let Some(hours) = params.hours {
-            cmd.push_str(&format!(" hours={}", hours));
+        policy::check_mutating(command)?;
+        let output = match &self.backend {
+            Backend::Bconsole(sessions) => sessions.execute_once(command, OutputMode::Text).await,
+            Backend::Director(sessions) => sessions.execute_once(command, OutputMode::Text).await,
+        }?;
+        Ok(self.redactor.redact(&output))
+    }
+
+    async fn execute_command(&self, command: &str) -> Result<String> {
+        let output = self.execute(command, OutputMode::Text).await?;
+        Ok(self.redactor.redact(&output))
+    }
+
+    // Redacts the records of an `.api` reply or of the table fallback before they are typed,
+    // except for the fields named in `keep`
+    fn records<T: DeserializeOwned>(&self, mut records: Value, keep: &[&str]) -> Result<Vec<T>> {
+        self.redactor.redact_value_except(&mut records, keep);
+        model::from_api(&records)
+    }
+
+    fn table<T: DeserializeOwned>(&self, output: &str, keep: &[&str]) -> Result<Vec<T>> {
+        let rows: Vec<Value> = model::from_table(output)?;
+        self.records(Value::Array(rows), keep)
+    }
+
+    // Lists catalog records, falling back to the table output for consoles that are not
+    // allowed to use `.api` (restricted consoles without it in their CommandACL)
+    async fn list<T: DeserializeOwned>(&self, command: &str, key: &str) -> Result<Vec<T>> {
+        self.list_keeping(command, key, &[]).await
+    }
+
+    // Like `list`, but leaves the fields named in `keep` unredacted
+    async fn list_keeping<T: DeserializeOwned>(
+        &self,
+        command: &str,
+        key: &str,
+        keep: &[&str],
+    ) -> Result<Vec<T>> {
+        let output = self.execute(command, OutputMode::Json).await?;
+        match parse_api_reply(&output) {
+            Some(result) => self.records(result?[key].take(), keep),
+            None => self.table(&output, keep),
         }
-        if params.last {
-            cmd.push_str(" last");
+    }
+
+    // `llist` prints a `key: value` block per record rather than a table, so without `.api`
+    // there is nothing to fall back to
+    async fn long_list<T: DeserializeOwned>(&self, command: &str, key: &str) -> Result<Vec<T>> {
+        let output = self.execute(command, OutputMode::Json).await?;
+        match parse_api_reply(&output) {
+            Some(result) => self.records(result?[key].take(), &[]),
+            None => anyhow::bail!(
+                "'{}' needs the .api command; allow it in the console's CommandACL",
+                command
+            ),
         }
-        if params.count {
-            cmd.push_str(" count");
+    }
+
+    pub async fn list_jobs(&self, params: JobListParams) -> Result<Vec<Job>> {
+        if params.long {
+            return self.long_list(&list_jobs_command(params), "jobs").await;
         }
+        self.list(&list_jobs_command(params), "jobs").await
+    }
 
-        self.execute_command(&cmd).await
+    pub async fn count_jobs(&self, params: JobListParams) -> Result<u64> {
+        let counts: Vec<JobCount> = self.list(&list_jobs_command(params), "jobs").await?;
+        Ok(counts.first().map(|count| count.count).unwrap_or_default())
     }
 
-    pub async fn get_job_status(&self, job_id: &str) -> Result<String> {
-        self.execute_command(&format!("list jobid={}", job_id))
-            .await
+    pub async fn get_job_status(&self, job_id: u64) -> Result<Vec<Job>> {
+        self.list(&format!("list jobid={}", job_id), "jobs").await
     }
 
-    pub async fn get_job_log(&self, job_id: &str) -> Result<String> {
-        self.execute_command(&format!("list joblog jobid={}", job_id))
-            .await
+    /// The full catalog record of a job, as `llist` prints it.
+    pub async fn get_job_record(&self, job_id: u64) -> Result<Option<Job>> {
+        let jobs: Vec<Job> = self
+            .long_list(&format!("llist jobid={}", job_id), "jobs")
+            .await?;
+        Ok(jobs.into_iter().next())
     }
 
-    pub async fn list_clients(&self) -> Result<String> {
-        self.execute_command("list clients").await
+    pub async fn get_job_log(&self, job_id: u64) -> Result<Vec<JobLogEntry>> {
+        let command = format!("list joblog jobid={}", job_id);
+        let output = self.execute(&command, OutputMode::Json).await?;
+        match parse_api_reply(&output) {
+            Some(result) => self.records(result?["joblog"].take(), &[]),
+            None => Ok(JobLogEntry::from_text(&self.redactor.redact(&output))),
+        }
     }
 
-    pub async fn list_filesets(&self) -> Result<String> {
-        self.execute_command("list filesets").await
+    pub async fn list_clients(&self) -> Result<Vec<Client>> {
+        self.list("list clients", "clients").await
     }
 
-    pub async fn list_pools(&self) -> Result<String> {
-        self.execute_command("list pools").await
+    pub async fn list_filesets(&self) -> Result<Vec<FileSet>> {
+        self.list("list filesets", "filesets").await
     }
 
-    pub async fn list_volumes(&self, pool: Option<&str>) -> Result<String> {
+    pub async fn list_pools(&self) -> Result<Vec<Pool>> {
+        self.list("list pools", "pools").await
+    }
+
+    pub async fn list_volumes(&self, pool: Option<&str>) -> Result<Vec<Volume>> {
         let cmd = if let Some(pool_name) = pool {
-            format!("list volumes pool={}", pool_name)
+            format!("list volumes pool={}", quote(pool_name))
         } else {
             "list volumes".to_owned()
         };
-        self.execute_command(&cmd).await
+        let output = self.execute(&cmd, OutputMode::Json).await?;
+        let Some(result) = parse_api_reply(&output) else {
+            return self.table(&output, &[]);
+        };
+
+        // Without a pool filter Bareos groups volumes by pool name
+        let mut volumes = Vec::new();
+        match result?["volumes"].take() {
+            Value::Object(pools) => {
+                for (pool_name, records) in pools {
+                    for mut volume in self.records::<Volume>(records, &[])? {
+                        volume.pool.get_or_insert_with(|| pool_name.clone());
+                        volumes.push(volume);
+                    }
+                }
+            }
+            records => {
+                for mut volume in self.records::<Volume>(records, &[])? {
+                    if let Some(pool_name) = pool {
+                        volume.pool.get_or_insert_with(|| pool_name.to_string());
+                    }
+                    volumes.push(volume);
+                }
+            }
+        }
+        Ok(volumes)
     }
 
-    pub async fn list_files(&self, job_id: &str) -> Result<String> {
-        self.execute_command(&format!("list files jobid={}", job_id))
+    pub async fn list_job_media(&self, job_id: u64) -> Result<Vec<JobMedia>> {
+        self.list(&format!("list jobmedia jobid={}", job_id), "jobmedia")
             .await
     }
 
+    /// The jobs whose files make up the state restored from `job_id`: the Full and any
+    /// Differential and Incrementals up to it.
+    pub async fn bvfs_get_jobids(&self, job_id: u64) -> Result<Vec<u64>> {
+        let output = self
+            .execute(
+                &format!(".bvfs_get_jobids jobid={}", job_id),
+                OutputMode::Json,
+            )
+            .await?;
+        let reply = match parse_api_reply(&output) {
+            Some(result) => result?,
+            None => Value::Null,
+        };
+        Ok(bvfs::job_ids(&reply, &output))
+    }
+
+    /// Builds the BVFS cache for `job_ids`; the other `bvfs_*` calls only see cached jobs.
+    pub async fn bvfs_update(&self, job_ids: &[u64]) -> Result<()> {
+        self.execute_command(&format!(".bvfs_update jobid={}", join_ids(job_ids)))
+            .await?;
+        Ok(())
+    }
+
+    /// Subdirectories of `path` (ending in `/`) in the merged tree of `job_ids`.
+    pub async fn bvfs_lsdirs(
+        &self,
+        job_ids: &[u64],
+        path: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<BvfsEntry>> {
+        let command = format!(
+            ".bvfs_lsdirs jobid={} path={} offset={} limit={}",
+            join_ids(job_ids),
+            quote(path),
+            offset,
+            limit
+        );
+        self.bvfs_list(&command, "directories", false).await
+    }
+
+    /// Files directly in `path` (ending in `/`) in the merged tree of `job_ids`.
+    pub async fn bvfs_lsfiles(
+        &self,
+        job_ids: &[u64],
+        path: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<BvfsEntry>> {
+        let command = format!(
+            ".bvfs_lsfiles jobid={} path={} offset={} limit={}",
+            join_ids(job_ids),
+            quote(path),
+            offset,
+            limit
+        );
+        self.bvfs_list(&command, "files", false).await
+    }
+
+    /// Every backed up version of the file `name` in directory `path` of `client`.
+    pub async fn bvfs_versions(
+        &self,
+        client: &str,
+        path: &str,
+        name: &str,
+    ) -> Result<Vec<BvfsEntry>> {
+        let command = format!(
+            ".bvfs_versions client={} path={} fname={}",
+            quote(client),
+            quote(path),
+            quote(name)
+        );
+        self.bvfs_list(&command, "versions", true).await
+    }
+
+    async fn bvfs_list(&self, command: &str, key: &str, versions: bool) -> Result<Vec<BvfsEntry>> {
+        let output = self.execute(command, OutputMode::Json).await?;
+        match parse_api_reply(&output) {
+            // File and directory names are catalog data and left out of the pattern pass
+            Some(result) => {
+                let mut records = result?[key].take();
+                self.redactor.redact_value_except(&mut records, &["name"]);
+                Ok(bvfs::from_api(&records))
+            }
+            None => {
+                let mut entries = bvfs::from_text(&output, versions);
+                for entry in &mut entries {
+                    entry.md5 = entry.md5.take().map(|md5| self.redactor.redact(&md5));
+                    entry.volume = entry
+                        .volume
+                        .take()
+                        .map(|volume| self.redactor.redact(&volume));
+                }
+                Ok(entries)
+            }
+        }
+    }
+
+    /// Names of the configured job resources.
+    pub async fn job_names(&self) -> Result<Vec<String>> {
+        self.resource_names(".jobs").await
+    }
+
+    /// Names of the configured client resources.
+    pub async fn client_names(&self) -> Result<Vec<String>> {
+        self.resource_names(".clients").await
+    }
+
+    /// Names of the configured pool resources.
+    pub async fn pool_names(&self) -> Result<Vec<String>> {
+        self.resource_names(".pools").await
+    }
+
+    /// Names of the volumes in the catalog.
+    pub async fn volume_names(&self) -> Result<Vec<String>> {
+        self.resource_names(".media").await
+    }
+
+    /// Names of the configured jobdefs resources.
+    pub async fn jobdefs_names(&self) -> Result<Vec<String>> {
+        self.resource_names(".jobdefs").await
+    }
+
+    /// Names of the configured fileset resources.
+    pub async fn fileset_names(&self) -> Result<Vec<String>> {
+        self.resource_names(".filesets").await
+    }
+
+    /// Names of the configured storage resources.
+    pub async fn storage_names(&self) -> Result<Vec<String>> {
+        self.resource_names(".storages").await
+    }
+
+    /// Names of the configured schedule resources.
+    pub async fn schedule_names(&self) -> Result<Vec<String>> {
+        self.resource_names(".schedule").await
+    }
+
+    /// The client, pool, FileSet and other defaults a job resource runs with, as `.defaults`
+    /// reports them, reused for a minute. Empty for an unknown job.
+    pub async fn job_defaults(&self, job: &str) -> Result<Arc<HashMap<String, String>>> {
+        if let Some((fetched, defaults)) = self.job_defaults.lock().unwrap().get(job) {
+            if fetched.elapsed() < JOB_DEFAULTS_TTL {
+                return Ok(Arc::clone(defaults));
+            }
+        }
+
+        let output = self
+            .execute_command(&format!(".defaults job={}", quote(job)))
+            .await?;
+        let defaults: Arc<HashMap<String, String>> = Arc::new(
+            output
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .collect(),
+        );
+        // Only configured jobs are kept, so made-up names cannot grow the cache
+        if !defaults.is_empty() {
+            let mut cache = self.job_defaults.lock().unwrap();
+            cache.retain(|_, (fetched, _)| fetched.elapsed() < JOB_DEFAULTS_TTL);
+            cache.insert(job.to_string(), (Instant::now(), Arc::clone(&defaults)));
+        }
+        Ok(defaults)
+    }
+
+    async fn resource_names(&self, command: &str) -> Result<Vec<String>> {
+        let output = self.execute_command(command).await?;
+        Ok(output
+            .lines()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Running, scheduled (for the next `days`, 1 by default) and terminated jobs.
+    pub async fn status_director(&self, days: Option<u32>) -> Result<DirectorStatus> {
+        let command = match days {
+            Some(days) => format!("status director days={}", days),
+            None => "status director".to_string(),
+        };
+        let output = self.execute_command(&command).await?;
+        Ok(DirectorStatus::parse(&output))
+    }
+
+    /// Asks the Director to contact the file daemon of `client`; an unreachable daemon is
+    /// reported in the result rather than as an error.
+    pub async fn status_client(&self, client: &str) -> Result<DaemonStatus> {
+        let output = self
+            .execute_command(&format!("status client={}", quote(client)))
+            .await?;
+        Ok(DaemonStatus::parse_client(client, &output))
+    }
+
+    /// Like `status_client`, for the storage daemon behind `storage`, including its devices.
+    pub async fn status_storage(&self, storage: &str) -> Result<DaemonStatus> {
+        let output = self
+            .execute_command(&format!("status storage={}", quote(storage)))
+            .await?;
+        Ok(DaemonStatus::parse_storage(storage, &output))
+    }
+
+    /// Shows the configuration of the `resource_type` resource called `name`, or of all
+    /// resources of that type.
+    pub async fn show(&self, resource_type: &str, name: Option<&str>) -> Result<String> {
+        let command = match name {
+            Some(name) => format!("show {}={}", resource_type, quote(name)),
+            // The Director matches keyword prefixes, so the plural lists all of them
+            None if resource_type.ends_with('s') => format!("show {}", resource_type),
+            None => format!("show {}s", resource_type),
+        };
+        self.execute_command(&command).await
+    }
+
     pub async fn show_job(&self, job_name: &str) -> Result<String> {
-        self.execute_command(&format!("show job={}", job_name))
-            .await
+        self.show("job", Some(job_name)).await
     }
 
     pub async fn show_jobdefs(&self, jobdefs_name: &str) -> Result<String> {
-        self.execute_command(&format!("show jobdefs={}", jobdefs_name))
-            .await
+        self.show("jobdefs", Some(jobdefs_name)).await
     }
 
     pub async fn show_schedule(&self, schedule_name: &str) -> Result<String> {
-        self.execute_command(&format!("show schedule={}", schedule_name))
-            .await
+        self.show("schedule", Some(schedule_name)).await
+    }
+}
+
+fn join_ids(job_ids: &[u64]) -> String {
+    job_ids
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn list_jobs_command(params: JobListParams) -> String {
+    let mut cmd = if params.long {
+        "llist jobs"
+    } else {
+        "list jobs"
+    }
+    .to_string();
+
+    // Pass all parameters to bconsole - it handles precedence and filtering
+    if let Some(job) = params.job {
+        cmd.push_str(&format!(" job={}", quote(&job)));
+    }
+    if let Some(client) = params.client {
+        cmd.push_str(&format!(" client={}", quote(&client)));
+    }
+    if let Some(jobstatus) = params.jobstatus {
+        cmd.push_str(&format!(" jobstatus={}", jobstatus));
+    }
+    if let Some(jobtype) = params.jobtype {
+        cmd.push_str(&format!(" jobtype={}", jobtype));
+    }
+    if let Some(joblevel) = params.joblevel {
+        cmd.push_str(&format!(" joblevel={}", joblevel));
+    }
+    if let Some(volume) = params.volume {
+        cmd.push_str(&format!(" volume={}", quote(&volume)));
+    }
+    if let Some(pool) = params.pool {
+        cmd.push_str(&format!(" pool={}", quote(&pool)));
+    }
+    if let Some(days) = params.days {
+        cmd.push_str(&format!(" days={}", days));
+    }
+    if let Some(hours) = params.hours {
+        cmd.push_str(&format!(" hours={}", hours));
+    }
+    if params.last {
+        cmd.push_str(" last");
+    }
+    if params.count {
+        cmd.push_str(" count");
+    }
+
+    cmd
+}
+
+fn parse_api_reply(output: &str) -> Option<Result<Value>> {
+    let start = output.find('{')?;
+    let reply = serde_json::Deserializer::from_str(&output[start..])
+        .into_iter::<Value>()
+        .next()?;
+
+    Some(
+        reply
+            .context("Invalid JSON in bconsole output")
+            .and_then(|mut reply| {
+                if let Some(error) = reply.get("error") {
+                    let messages = error["data"]["messages"]["error"]
+                        .as_array()
+                        .map(|messages| {
+                            messages
+                                .iter()
+                                .filter_map(Value::as_str)
+                                .map(str::trim_end)
+                                .collect::<Vec<_>>()
+                                .join(" ")
+                        })
+                        .unwrap_or_default();
+                    anyhow::bail!(
+                        "bconsole command failed: {} {}",
+                        error["message"].as_str().unwrap_or("unknown error"),
+                        messages
+                    );
+                }
+                Ok(reply["result"].take())
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::FakeBconsole;
+
+    #[tokio::test]
+    async fn reads_long_job_records_only_through_the_api() {
+        let fake = FakeBconsole::new(
+            r#"
+  "llist jobid=7") echo '{"jsonrpc":"2.0","id":null,"result":{"jobs":[{"jobid":"7","name":"backup-web","type":"B","level":"I","jobstatus":"T","jobtdate":"1700000000","purgedfiles":"0"}]}}' ;;
+  "llist jobid=8") printf '           jobid: 8\n             job: backup-web.2024-03-01\n            name: backup-web\n' ;;"#,
+        );
+        let client = fake.client();
+        let job = client.get_job_record(7).await.unwrap().unwrap();
+        assert_eq!(job.job_tdate, Some(1_700_000_000));
+        assert_eq!(job.purged_files, Some(false));
+
+        let error = client.get_job_record(8).await.unwrap_err();
+        assert!(error.to_string().contains("needs the .api command"));
+    }
+
+    #[tokio::test]
+    async fn leaves_catalog_names_to_the_caller() {
+        let fake = FakeBconsole::new(
+            r#"
+  ".bvfs_lsdirs jobid=5 path=/srv/ offset=0 limit=10") echo '{"result":{"directories":[{"pathid":"2","fileid":"0","jobid":"5","lstat":"A","name":"password: old/"}]}}' ;;
+  ".bvfs_lsfiles jobid=5 path=/srv/ offset=0 limit=10") printf '2\t9\t5\tA\tsecret=x.conf\n' ;;"#,
+        );
+        let client = fake.client();
+        assert_eq!(
+            client.bvfs_lsdirs(&[5], "/srv/", 0, 10).await.unwrap()[0].name,
+            "password: old/"
+        );
+        assert_eq!(
+            client.bvfs_lsfiles(&[5], "/srv/", 0, 10).await.unwrap()[0].name,
+            "secret=x.conf"
+        );
+    }
+
+    #[tokio::test]
+    async fn reuses_the_defaults_of_configured_jobs() {
+        let fake = FakeBconsole::new(
+            r#"
+  ".defaults job=backup-web") printf 'job=backup-web\nclient=web01-fd\n' ;;"#,
+        );
+        let client = fake.client();
+        for _ in 0..2 {
+            assert_eq!(
+                client.job_defaults("backup-web").await.unwrap()["client"],
+                "web01-fd"
+            );
+            assert!(client.job_defaults("no-such-job").await.unwrap().is_empty());
+        }
+        assert_eq!(
+            fake.commands(),
+            vec![
+                ".defaults job=backup-web",
+                ".defaults job=no-such-job",
+                ".defaults job=no-such-job"
+            ]
+        );
     }
 }
@@ -0,0 +1,233 @@
+//! Records of the Bareos virtual file system (`.bvfs_*` commands), which browses the files
+//! of a set of backup jobs as one merged directory tree.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// A directory, file or file version as listed by `.bvfs_lsdirs`, `.bvfs_lsfiles` or
+/// `.bvfs_versions`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BvfsEntry {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    pub pathid: u64,
+    pub fileid: u64,
+    pub jobid: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Modification time in UTC, `YYYY-MM-DD HH:MM:SS`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub md5: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+}
+
+impl BvfsEntry {
+    pub fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "pathid": { "type": "integer" },
+                "fileid": { "type": "integer" },
+                "jobid": { "type": "integer" },
+                "size": { "type": "integer" },
+                "mtime": { "type": "string" },
+                "md5": { "type": "string" },
+                "volume": { "type": "string" }
+            },
+            "required": ["pathid", "fileid", "jobid"]
+        })
+    }
+
+    fn new(pathid: u64, fileid: u64, jobid: u64, lstat: &str, name: &str) -> Self {
+        let stat = decode_lstat(lstat);
+        BvfsEntry {
+            name: name.to_string(),
+            pathid,
+            fileid,
+            jobid,
+            // Fields 7 and 11 of a stat record are st_size and st_mtime
+            size: stat.get(7).and_then(|size| u64::try_from(*size).ok()),
+            mtime: stat
+                .get(11)
+                .filter(|mtime| **mtime > 0)
+                .map(|mtime| utc(*mtime)),
+            md5: None,
+            volume: None,
+        }
+    }
+}
+
+fn number(value: &Value) -> u64 {
+    match value {
+        Value::Number(number) => number.as_u64().unwrap_or_default(),
+        Value::String(text) => text.parse().unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    value
+        .as_str()
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+/// Entries from an `.api 2` result array.
+pub fn from_api(records: &Value) -> Vec<BvfsEntry> {
+    records
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|record| {
+            let mut entry = BvfsEntry::new(
+                number(&record["pathid"]),
+                number(&record["fileid"]),
+                number(&record["jobid"]),
+                record["lstat"].as_str().unwrap_or(""),
+                record["name"].as_str().unwrap_or(""),
+            );
+            entry.md5 = text(&record["md5"]);
+            entry.volume = text(&record["volumename"]);
+            entry
+        })
+        .collect()
+}
+
+/// Entries from the tab separated text output: `PathId FileId JobId LStat Name` for
+/// directories and files, `PathId FileId JobId LStat MD5 VolName InChanger` for versions.
+pub fn from_text(output: &str, versions: bool) -> Vec<BvfsEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split('\t').collect();
+            if columns.len() < 5 {
+                return None;
+            }
+            let id = |index: usize| columns[index].trim().parse::<u64>().ok();
+            let name = if versions { "" } else { columns[4] };
+            let mut entry = BvfsEntry::new(id(0)?, id(1)?, id(2)?, columns[3], name);
+            if versions {
+                entry.md5 = Some(columns[4].to_string()).filter(|md5| !md5.is_empty());
+                entry.volume = columns
+                    .get(5)
+                    .map(|volume| volume.to_string())
+                    .filter(|volume| !volume.is_empty());
+            }
+            Some(entry)
+        })
+        .collect()
+}
+
+/// Job IDs from `.bvfs_get_jobids`, either `1,2,3` or an `.api 2` list of `{"id": ...}`.
+pub fn job_ids(reply: &Value, output: &str) -> Vec<u64> {
+    if let Some(records) = reply["jobids"].as_array() {
+        return records
+            .iter()
+            .map(|record| match record {
+                Value::Object(_) => number(&record["id"]),
+                other => number(other),
+            })
+            .filter(|id| *id > 0)
+            .collect();
+    }
+    output
+        .lines()
+        .rev()
+        .find(|line| line.trim().starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|id| id.trim().parse().ok())
+        .collect()
+}
+
+// LStat is a space separated list of numbers in Bareos' own base64 digits
+fn decode_lstat(lstat: &str) -> Vec<i64> {
+    const DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    lstat
+        .split_whitespace()
+        .map(|field| {
+            let (negative, field) = match field.strip_prefix('-') {
+                Some(field) => (true, field),
+                None => (false, field),
+            };
+            let value = field.bytes().fold(0i64, |value, digit| {
+                let digit = DIGITS.iter().position(|d| *d == digit).unwrap_or(0) as i64;
+                value.wrapping_mul(64).wrapping_add(digit)
+            });
+            if negative {
+                -value
+            } else {
+                value
+            }
+        })
+        .collect()
+}
+
+// Seconds since the epoch as a UTC timestamp, without pulling in a date library
+fn utc(seconds: i64) -> String {
+    let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    // Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_lstat() {
+        // st_size 1234 ("TS") and st_mtime 1700000000 ("BlU/EA")
+        let entry = BvfsEntry::new(
+            1,
+            2,
+            3,
+            "A A IH/ B A A A TS BAA C BlU/EA BlU/EA BlU/EA A A C",
+            "nginx.conf",
+        );
+        assert_eq!(entry.size, Some(1234));
+        assert_eq!(entry.mtime.as_deref(), Some("2023-11-14 22:13:20"));
+    }
+
+    #[test]
+    fn parses_text_output() {
+        let entries = from_text("12\t34\t5\tA A IH/ B A A A TS\tnginx.conf\n", false);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "nginx.conf");
+        assert_eq!(entries[0].jobid, 5);
+
+        let versions = from_text("12\t34\t5\tA A IH/ B A A A TS\tabcd\tFull-0001\t0\n", true);
+        assert_eq!(versions[0].volume.as_deref(), Some("Full-0001"));
+        assert_eq!(versions[0].md5.as_deref(), Some("abcd"));
+    }
+
+    #[test]
+    fn parses_job_ids() {
+        assert_eq!(job_ids(&Value::Null, "1,4,7\n"), vec![1, 4, 7]);
+        assert_eq!(
+            job_ids(&json!({"jobids": [{"id": "1"}, {"id": "4"}]}), ""),
+            vec![1, 4]
+        );
+    }
+}
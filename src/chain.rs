@@ -0,0 +1,198 @@
+//! Works out which earlier backups a backup job depends on, the way the Director picks them
+//! for an accurate restore: the last good Full of the same client and FileSet, the last good
+//! Differential after it, and the good Incrementals after that.
+
+use crate::model::{Job, JobLevel, JobStatus, JobType};
+
+/// The jobs a backup builds on, oldest first, ending with the job itself.
+#[derive(Debug)]
+pub struct Chain<'a> {
+    pub full: Option<&'a Job>,
+    pub differential: Option<&'a Job>,
+    pub incrementals: Vec<&'a Job>,
+    /// The most recent Full attempt before the job, if it did not succeed.
+    pub failed_full: Option<&'a Job>,
+}
+
+impl<'a> Chain<'a> {
+    pub fn jobs(&self) -> Vec<&'a Job> {
+        self.full
+            .into_iter()
+            .chain(self.differential)
+            .chain(self.incrementals.iter().copied())
+            .collect()
+    }
+}
+
+pub fn is_successful(job: &Job) -> bool {
+    matches!(job.status, JobStatus::Terminated | JobStatus::Warnings)
+}
+
+fn is_full(job: &Job) -> bool {
+    matches!(job.level, JobLevel::Full | JobLevel::VirtualFull)
+}
+
+// Client and FileSet IDs when `llist` gave them, names otherwise
+fn same_backup(a: &Job, b: &Job) -> bool {
+    let client = match (a.client_id, b.client_id) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.client == b.client,
+    };
+    let fileset = match (a.fileset_id, b.fileset_id) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.fileset == b.fileset,
+    };
+    client && fileset
+}
+
+/// The order backups ran in: JobTDate, the job's start as a Unix time, with the catalog time
+/// as the fallback.
+pub fn order(job: &Job) -> (u64, Option<&str>, u64) {
+    (
+        job.job_tdate.unwrap_or_default(),
+        job.start_time.as_deref(),
+        job.job_id,
+    )
+}
+
+/// Finds the chain `job` belongs to among `history`, the backups of its client.
+pub fn resolve<'a>(job: &'a Job, history: &'a [Job]) -> Chain<'a> {
+    let mut earlier: Vec<&Job> = history
+        .iter()
+        .filter(|other| {
+            other.job_id != job.job_id
+                && other.job_type == JobType::Backup
+                && same_backup(job, other)
+                && order(other) < order(job)
+        })
+        .collect();
+    earlier.sort_by(|a, b| order(a).cmp(&order(b)));
+
+    if is_full(job) {
+        return Chain {
+            full: Some(job),
+            differential: None,
+            incrementals: Vec::new(),
+            failed_full: None,
+        };
+    }
+
+    let full = earlier
+        .iter()
+        .rev()
+        .find(|other| is_full(other) && is_successful(other))
+        .copied();
+    let failed_full = earlier
+        .iter()
+        .rev()
+        .find(|other| is_full(other))
+        .copied()
+        .filter(|other| !is_successful(other));
+    let after =
+        |since: Option<&Job>, other: &Job| since.is_none_or(|since| order(other) > order(since));
+
+    let differential = match job.level {
+        JobLevel::Incremental => earlier
+            .iter()
+            .rev()
+            .find(|other| {
+                other.level == JobLevel::Differential && is_successful(other) && after(full, other)
+            })
+            .copied(),
+        _ => None,
+    };
+    let base = differential.or(full);
+    let mut incrementals: Vec<&Job> = match job.level {
+        JobLevel::Incremental => earlier
+            .iter()
+            .filter(|other| {
+                other.level == JobLevel::Incremental && is_successful(other) && after(base, other)
+            })
+            .copied()
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    match job.level {
+        JobLevel::Differential => Chain {
+            full,
+            differential: Some(job),
+            incrementals,
+            failed_full,
+        },
+        _ => {
+            incrementals.push(job);
+            Chain {
+                full,
+                differential,
+                incrementals,
+                failed_full,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn job(id: u64, level: &str, status: &str, tdate: u64) -> Job {
+        serde_json::from_value(json!({
+            "jobid": id,
+            "name": "backup-web",
+            "type": "B",
+            "level": level,
+            "jobstatus": status,
+            "jobtdate": tdate,
+            "clientid": 1,
+            "filesetid": 1
+        }))
+        .unwrap()
+    }
+
+    fn ids(chain: &Chain) -> Vec<u64> {
+        chain.jobs().iter().map(|job| job.job_id).collect()
+    }
+
+    #[test]
+    fn builds_full_differential_incremental_chain() {
+        let history = vec![
+            job(1, "F", "T", 100),
+            job(2, "I", "T", 200),
+            job(3, "D", "T", 300),
+            job(4, "I", "E", 400),
+            job(5, "I", "T", 500),
+            job(6, "I", "T", 600),
+        ];
+        let chain = resolve(&history[5], &history);
+        assert_eq!(ids(&chain), vec![1, 3, 5, 6]);
+        assert!(chain.failed_full.is_none());
+
+        let chain = resolve(&history[2], &history);
+        assert_eq!(ids(&chain), vec![1, 3]);
+    }
+
+    #[test]
+    fn skips_failed_fulls_and_other_filesets() {
+        let mut other = job(3, "F", "T", 250);
+        other.fileset_id = Some(2);
+        let history = vec![
+            job(1, "F", "T", 100),
+            job(2, "F", "f", 200),
+            other,
+            job(4, "I", "T", 300),
+        ];
+        let chain = resolve(&history[3], &history);
+        assert_eq!(ids(&chain), vec![1, 4]);
+        assert_eq!(chain.failed_full.map(|job| job.job_id), Some(2));
+    }
+
+    #[test]
+    fn reports_missing_full() {
+        let history = vec![job(1, "F", "A", 100), job(2, "I", "T", 200)];
+        let chain = resolve(&history[1], &history);
+        assert!(chain.full.is_none());
+        assert_eq!(ids(&chain), vec![2]);
+    }
+}
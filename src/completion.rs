@@ -0,0 +1,156 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::auth::Identity;
+use crate::bareos::BareosClient;
+
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+// The MCP spec caps a completion response at 100 values
+const MAX_VALUES: usize = 100;
+
+// When the names were fetched, and the sorted names
+type CachedNames = (Instant, Arc<Vec<String>>);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Jobs,
+    Clients,
+    Pools,
+    Volumes,
+    JobDefs,
+    Schedules,
+    Storages,
+    FileSets,
+}
+
+impl Kind {
+    fn for_argument(reference: &Value, argument: &str) -> Option<Kind> {
+        // Resource templates all call their argument `name`; the URI says what it names
+        if argument == "name" && reference["type"] == "ref/resource" {
+            let uri = reference["uri"].as_str().unwrap_or("");
+            return [
+                ("bareos://job/", Kind::Jobs),
+                ("bareos://schedule/", Kind::Schedules),
+                ("bareos://pool/", Kind::Pools),
+            ]
+            .into_iter()
+            .find(|(prefix, _)| uri.starts_with(prefix))
+            .map(|(_, kind)| kind);
+        }
+
+        match argument {
+            "job" | "job_name" => Some(Kind::Jobs),
+            "client" => Some(Kind::Clients),
+            "pool" => Some(Kind::Pools),
+            "volume" => Some(Kind::Volumes),
+            "jobdefs_name" => Some(Kind::JobDefs),
+            "schedule_name" => Some(Kind::Schedules),
+            "storage" => Some(Kind::Storages),
+            "fileset" => Some(Kind::FileSets),
+            _ => None,
+        }
+    }
+
+    async fn fetch(self, client: &BareosClient) -> Result<Vec<String>> {
+        match self {
+            Kind::Jobs => client.job_names().await,
+            Kind::Clients => client.client_names().await,
+            Kind::Pools => client.pool_names().await,
+            Kind::Volumes => client.volume_names().await,
+            Kind::JobDefs => client.jobdefs_names().await,
+            Kind::Schedules => client.schedule_names().await,
+            Kind::Storages => client.storage_names().await,
+            Kind::FileSets => client.fileset_names().await,
+        }
+    }
+
+    fn visible_to(self, identity: &Identity, name: &str) -> bool {
+        match self {
+            Kind::Jobs => identity.allows_job(Some(name)),
+            Kind::Clients => identity.reaches_client(Some(name)),
+            Kind::Pools => identity.allows_pool(Some(name)),
+            Kind::Storages => identity.allows_storage(Some(name)),
+            // Volume names alone do not say which pool they belong to
+            Kind::Volumes => identity.allows_volume(None),
+            _ => true,
+        }
+    }
+}
+
+/// Answers `completion/complete` from the Director's resource names, cached for a while so
+/// typing does not send a dot command per keystroke.
+pub struct Completions {
+    ttl: Duration,
+    cache: Mutex<HashMap<Kind, CachedNames>>,
+}
+
+impl Default for Completions {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_TTL)
+    }
+}
+
+impl Completions {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn names(&self, client: &BareosClient, kind: Kind) -> Result<Arc<Vec<String>>> {
+        if let Some((fetched, names)) = self.cache.lock().unwrap().get(&kind) {
+            if fetched.elapsed() < self.ttl {
+                return Ok(Arc::clone(names));
+            }
+        }
+
+        let mut names = kind.fetch(client).await?;
+        names.sort();
+        names.dedup();
+        let names = Arc::new(names);
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(kind, (Instant::now(), Arc::clone(&names)));
+        Ok(names)
+    }
+
+    /// Completes the argument in the `params` of a `completion/complete` request.
+    pub async fn complete(
+        &self,
+        client: &BareosClient,
+        identity: &Identity,
+        params: &Value,
+    ) -> Result<Value> {
+        let argument = params["argument"]["name"].as_str().unwrap_or("");
+        let prefix = params["argument"]["value"]
+            .as_str()
+            .unwrap_or("")
+            .to_lowercase();
+
+        let matches: Vec<String> = match Kind::for_argument(&params["ref"], argument) {
+            Some(kind) => self
+                .names(client, kind)
+                .await?
+                .iter()
+                .filter(|name| name.to_lowercase().starts_with(&prefix))
+                .filter(|name| kind.visible_to(identity, name))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Ok(json!({
+            "completion": {
+                "values": matches.iter().take(MAX_VALUES).collect::<Vec<_>>(),
+                "total": matches.len(),
+                "hasMore": matches.len() > MAX_VALUES
+            }
+        }))
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::validate::InvalidParams;
+
+pub const DEFAULT_CONFIRMATION_TTL: Duration = Duration::from_secs(120);
+
+/// 128 random bits, hex-encoded.
+pub(crate) fn random_token() -> anyhow::Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to generate random token: {}", e))?;
+    Ok(bytes.iter().fold(String::new(), |mut token, byte| {
+        let _ = write!(token, "{:02x}", byte);
+        token
+    }))
+}
+
+/// Who a token was issued to: the transport session and the identity behind it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Holder {
+    pub session: String,
+    pub identity: String,
+}
+
+impl Holder {
+    pub fn new(session: &str, identity: &str) -> Self {
+        Self {
+            session: session.to_string(),
+            identity: identity.to_string(),
+        }
+    }
+}
+
+struct Pending {
+    holder: Holder,
+    tool: String,
+    command: String,
+    expires: Instant,
+}
+
+/// Short-lived, single-use tokens that a mutating tool call must present to execute.
+///
+/// A token is bound to its holder, the tool and the exact bconsole command its arguments
+/// resolved to, so it cannot be replayed from another session or with different arguments.
+pub struct Confirmations {
+    ttl: Duration,
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl Default for Confirmations {
+    fn default() -> Self {
+        Self::new(DEFAULT_CONFIRMATION_TTL)
+    }
+}
+
+impl Confirmations {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Issues `holder` a token for running `command` through `tool`.
+    pub fn issue(&self, holder: &Holder, tool: &str, command: &str) -> anyhow::Result<String> {
+        let token = random_token()?;
+
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, confirmation| confirmation.expires > now);
+        pending.insert(
+            token.clone(),
+            Pending {
+                holder: holder.clone(),
+                tool: tool.to_string(),
+                command: command.to_string(),
+                expires: now + self.ttl,
+            },
+        );
+        Ok(token)
+    }
+
+    /// Consumes `token` if it was issued to `holder` for `tool` and `command` and has not
+    /// expired.
+    ///
+    /// A token presented with different arguments stays valid, so a mistyped call does not
+    /// cost the caller its confirmation.
+    pub fn redeem(
+        &self,
+        token: &str,
+        holder: &Holder,
+        tool: &str,
+        command: &str,
+    ) -> Result<(), InvalidParams> {
+        let invalid = |reason: &str| InvalidParams {
+            argument: "confirmation_token".to_string(),
+            reason: reason.to_string(),
+        };
+
+        let mut pending = self.pending.lock().unwrap();
+        // Another holder's token is reported as unknown, so its existence does not leak
+        let confirmation = pending
+            .get(token)
+            .filter(|confirmation| confirmation.holder == *holder)
+            .ok_or_else(|| {
+                invalid("unknown or already used; call the tool without it to get a new one")
+            })?;
+
+        if confirmation.expires <= Instant::now() {
+            pending.remove(token);
+            return Err(invalid(
+                "expired; call the tool without it to get a new one",
+            ));
+        }
+        if confirmation.tool != tool || confirmation.command != command {
+            return Err(invalid(
+                "was issued for different arguments; call the tool without it to get a new one",
+            ));
+        }
+        pending.remove(token);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holder() -> Holder {
+        Holder::new("session-1", "ops")
+    }
+
+    #[test]
+    fn tokens_are_single_use() {
+        let confirmations = Confirmations::default();
+        let token = confirmations
+            .issue(&holder(), "cancel_job", "cancel jobid=7")
+            .unwrap();
+
+        confirmations
+            .redeem(&token, &holder(), "cancel_job", "cancel jobid=7")
+            .unwrap();
+        let err = confirmations
+            .redeem(&token, &holder(), "cancel_job", "cancel jobid=7")
+            .unwrap_err();
+        assert!(err.reason.starts_with("unknown or already used"));
+    }
+
+    #[test]
+    fn expired_tokens_are_refused() {
+        let confirmations = Confirmations::new(Duration::ZERO);
+        let token = confirmations
+            .issue(&holder(), "cancel_job", "cancel jobid=7")
+            .unwrap();
+
+        let err = confirmations
+            .redeem(&token, &holder(), "cancel_job", "cancel jobid=7")
+            .unwrap_err();
+        assert!(err.reason.starts_with("expired"));
+        let err = confirmations
+            .redeem(&token, &holder(), "cancel_job", "cancel jobid=7")
+            .unwrap_err();
+        assert!(err.reason.starts_with("unknown or already used"));
+    }
+
+    #[test]
+    fn mismatched_arguments_keep_the_token() {
+        let confirmations = Confirmations::default();
+        let token = confirmations
+            .issue(&holder(), "cancel_job", "cancel jobid=7")
+            .unwrap();
+
+        for (tool, command) in [
+            ("cancel_job", "cancel jobid=8"),
+            ("rerun_job", "cancel jobid=7"),
+        ] {
+            let err = confirmations
+                .redeem(&token, &holder(), tool, command)
+                .unwrap_err();
+            assert!(err.reason.starts_with("was issued for different arguments"));
+        }
+        confirmations
+            .redeem(&token, &holder(), "cancel_job", "cancel jobid=7")
+            .unwrap();
+    }
+
+    #[test]
+    fn tokens_only_work_for_their_holder() {
+        let confirmations = Confirmations::default();
+        let token = confirmations
+            .issue(&holder(), "cancel_job", "cancel jobid=7")
+            .unwrap();
+
+        for other in [
+            Holder::new("session-2", "ops"),
+            Holder::new("session-1", "web-team"),
+        ] {
+            let err = confirmations
+                .redeem(&token, &other, "cancel_job", "cancel jobid=7")
+                .unwrap_err();
+            assert!(err.reason.starts_with("unknown or already used"));
+        }
+        confirmations
+            .redeem(&token, &holder(), "cancel_job", "cancel jobid=7")
+            .unwrap();
+    }
+}
@@ -0,0 +1,540 @@
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+use crate::session::{Connect, NotSent, Session};
+
+const DEFAULT_DIRECTOR_PORT: u16 = 9101;
+const DEFAULT_CONSOLE_NAME: &str = "*UserAgent*";
+const CLIENT_VERSION: &str = "23.0.0";
+const MAX_MESSAGE_LENGTH: i32 = 1_000_000;
+
+// Signals are sent as negative message lengths (see bsock.h in the Bareos sources)
+const BNET_EOD: i32 = -1;
+const BNET_TERMINATE: i32 = -4;
+const BNET_HEARTBEAT: i32 = -6;
+const BNET_HB_RESPONSE: i32 = -7;
+const BNET_MAIN_PROMPT: i32 = -18;
+const BNET_SELECT_INPUT: i32 = -19;
+const BNET_YESNO: i32 = -24;
+const BNET_SUB_PROMPT: i32 = -27;
+const BNET_TEXT_INPUT: i32 = -28;
+
+const AUTH_OK: &str = "1000 OK auth\n";
+const AUTH_FAILED: &str = "1999 Authorization failed.\n";
+const HELLO_OK: &str = "1000 OK:";
+
+const BASE64_DIGITS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+enum Packet {
+    Message(String),
+    Signal(i32),
+}
+
+/// Connection settings for talking to the Director over the network instead of via bconsole.
+pub struct Director {
+    pub host: String,
+    pub port: u16,
+    pub console_name: Option<String>,
+    // The hex MD5 the Director keys CRAM-MD5 and TLS-PSK with, never the plain password
+    password: String,
+    pub tls_psk: bool,
+}
+
+impl Director {
+    /// `password` is the console password as configured, or `[md5]` followed by its hex MD5.
+    pub fn new(host: impl Into<String>, port: u16, password: &str) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            console_name: None,
+            password: hash_password(password),
+            tls_psk: false,
+        }
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let host =
+            std::env::var("BAREOS_DIRECTOR_HOST").unwrap_or_else(|_| "localhost".to_string());
+        let port = match std::env::var("BAREOS_DIRECTOR_PORT") {
+            Ok(port) => port
+                .parse()
+                .context("BAREOS_DIRECTOR_PORT is not a valid port")?,
+            Err(_) => DEFAULT_DIRECTOR_PORT,
+        };
+        let console_name = std::env::var("BAREOS_CONSOLE_NAME").ok();
+        let password = std::env::var("BAREOS_CONSOLE_PASSWORD")
+            .context("BAREOS_CONSOLE_PASSWORD must be set when using the director backend")?;
+        let tls_psk = std::env::var("BAREOS_DIRECTOR_TLS_PSK")
+            .map(|value| value == "true" || value == "1")
+            .unwrap_or(false);
+
+        Ok(Self {
+            console_name,
+            tls_psk,
+            ..Self::new(host, port, &password)
+        })
+    }
+
+    fn console_name(&self) -> &str {
+        self.console_name.as_deref().unwrap_or(DEFAULT_CONSOLE_NAME)
+    }
+
+    #[cfg(feature = "tls-psk")]
+    async fn start_tls(&self, tcp: TcpStream) -> Result<Box<dyn Stream>> {
+        use openssl::ssl::{Ssl, SslContext, SslMethod, SslVersion};
+        use std::pin::Pin;
+
+        // The Director looks up the PSK by "<resource type><record separator><name>"
+        let identity = format!("R_CONSOLE\x1e{}", self.console_name());
+        let psk = self.password.clone();
+
+        let mut context = SslContext::builder(SslMethod::tls_client())?;
+        context.set_max_proto_version(Some(SslVersion::TLS1_2))?;
+        context.set_cipher_list("PSK")?;
+        context.set_psk_client_callback(move |_, _, identity_buf, psk_buf| {
+            let identity = identity.as_bytes();
+            if identity.len() >= identity_buf.len() || psk.len() > psk_buf.len() {
+                return Err(openssl::error::ErrorStack::get());
+            }
+            identity_buf[..identity.len()].copy_from_slice(identity);
+            identity_buf[identity.len()] = 0;
+            psk_buf[..psk.len()].copy_from_slice(psk.as_bytes());
+            Ok(psk.len())
+        });
+
+        let ssl = Ssl::new(&context.build())?;
+        let mut stream = tokio_openssl::SslStream::new(ssl, tcp)?;
+        Pin::new(&mut stream)
+            .connect()
+            .await
+            .context("TLS-PSK handshake with Director failed")?;
+        Ok(Box::new(stream))
+    }
+
+    #[cfg(not(feature = "tls-psk"))]
+    async fn start_tls(&self, _tcp: TcpStream) -> Result<Box<dyn Stream>> {
+        anyhow::bail!(
+            "TLS-PSK requested but bareos-mcp-server was built without the tls-psk feature"
+        )
+    }
+}
+
+impl Connect for Director {
+    type Session = DirectorSession;
+
+    async fn connect(&self) -> Result<DirectorSession> {
+        let tcp = TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to connect to Director at {}:{}",
+                    self.host, self.port
+                )
+            })?;
+
+        let stream: Box<dyn Stream> = if self.tls_psk {
+            self.start_tls(tcp).await?
+        } else {
+            Box::new(tcp)
+        };
+
+        let mut session = DirectorSession { stream };
+        session.authenticate(self).await?;
+        Ok(session)
+    }
+}
+
+pub struct DirectorSession {
+    stream: Box<dyn Stream>,
+}
+
+impl DirectorSession {
+    async fn send(&mut self, message: &str) -> Result<()> {
+        let length = i32::try_from(message.len()).context("Message too long")?;
+        self.stream.write_all(&length.to_be_bytes()).await?;
+        self.stream.write_all(message.as_bytes()).await?;
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    async fn signal(&mut self, signal: i32) -> Result<()> {
+        self.stream.write_all(&signal.to_be_bytes()).await?;
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> Result<Packet> {
+        let mut header = [0u8; 4];
+        self.stream
+            .read_exact(&mut header)
+            .await
+            .context("Director closed the connection")?;
+
+        let length = i32::from_be_bytes(header);
+        if length < 0 {
+            return Ok(Packet::Signal(length));
+        }
+        if length > MAX_MESSAGE_LENGTH {
+            anyhow::bail!("Director sent an oversized message ({} bytes)", length);
+        }
+
+        let mut body = vec![0u8; length as usize];
+        self.stream
+            .read_exact(&mut body)
+            .await
+            .context("Director closed the connection")?;
+        Ok(Packet::Message(String::from_utf8_lossy(&body).into_owned()))
+    }
+
+    async fn recv_message(&mut self) -> Result<String> {
+        loop {
+            match self.recv().await? {
+                Packet::Message(message) => return Ok(message),
+                Packet::Signal(BNET_HEARTBEAT) => self.signal(BNET_HB_RESPONSE).await?,
+                Packet::Signal(signal) => {
+                    anyhow::bail!(
+                        "Unexpected signal {} from Director during handshake",
+                        signal
+                    )
+                }
+            }
+        }
+    }
+
+    async fn authenticate(&mut self, director: &Director) -> Result<()> {
+        let name = director.console_name().replace(' ', "\x01");
+        self.send(&format!(
+            "Hello {} calling version {}\n",
+            name, CLIENT_VERSION
+        ))
+        .await?;
+
+        // The Director challenges us first, then we challenge the Director
+        let challenge = self.recv_message().await?;
+        let (challenge, compatible) = parse_challenge(&challenge)
+            .with_context(|| format!("Unexpected Director handshake: {}", challenge.trim_end()))?;
+        self.send(&cram_md5_response(
+            &challenge,
+            &director.password,
+            compatible,
+        ))
+        .await?;
+
+        let reply = self.recv_message().await?;
+        if !reply.starts_with(AUTH_OK.trim_end()) {
+            anyhow::bail!(
+                "Director rejected the console password: {}",
+                reply.trim_end()
+            );
+        }
+
+        let challenge = format!(
+            "<{}.{}@bareos-mcp-server>",
+            random_u32()?,
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default()
+        );
+        let ssl = if director.tls_psk { 2 } else { 0 };
+        self.send(&format!("auth cram-md5c {} ssl={}\n", challenge, ssl))
+            .await?;
+
+        let response = self.recv_message().await?;
+        let response = response.trim_end_matches(['\n', '\0']);
+        if response != cram_md5_response(&challenge, &director.password, true)
+            && response != cram_md5_response(&challenge, &director.password, false)
+        {
+            self.send(AUTH_FAILED).await?;
+            anyhow::bail!("Director failed to authenticate itself");
+        }
+        self.send(AUTH_OK).await?;
+
+        let hello = self.recv_message().await?;
+        if !hello.starts_with(HELLO_OK) {
+            anyhow::bail!("Director refused the connection: {}", hello.trim_end());
+        }
+        debug!("Connected to Director: {}", hello.trim_end());
+
+        Ok(())
+    }
+}
+
+impl Session for DirectorSession {
+    async fn execute(&mut self, command: &str) -> Result<String> {
+        self.send(command).await.context(NotSent)?;
+
+        let mut output = String::new();
+        let mut interactive = false;
+        loop {
+            match self.recv().await? {
+                Packet::Message(message) => output.push_str(&message),
+                Packet::Signal(BNET_EOD) | Packet::Signal(BNET_MAIN_PROMPT) => break,
+                Packet::Signal(BNET_HEARTBEAT) => self.signal(BNET_HB_RESPONSE).await?,
+                Packet::Signal(BNET_TERMINATE) => {
+                    anyhow::bail!("Director terminated the connection")
+                }
+                Packet::Signal(
+                    BNET_SUB_PROMPT | BNET_TEXT_INPUT | BNET_SELECT_INPUT | BNET_YESNO,
+                ) => {
+                    // A period cancels the pending question
+                    interactive = true;
+                    self.send(".").await?;
+                }
+                Packet::Signal(_) => {}
+            }
+        }
+
+        if interactive {
+            anyhow::bail!(
+                "Command '{}' asked for interactive input: {}",
+                command,
+                output.trim_end()
+            );
+        }
+        Ok(output)
+    }
+}
+
+// Console passwords are stored as the hex MD5 of the configured value unless already hashed
+fn hash_password(password: &str) -> String {
+    match password.strip_prefix("[md5]") {
+        Some(hashed) => hashed.to_string(),
+        None => format!("{:x}", Md5::digest(password.as_bytes())),
+    }
+}
+
+fn parse_challenge(message: &str) -> Option<(String, bool)> {
+    let (rest, compatible) = if let Some(rest) = message.strip_prefix("auth cram-md5c ") {
+        (rest, true)
+    } else {
+        (message.strip_prefix("auth cram-md5 ")?, false)
+    };
+    let challenge = rest.split_whitespace().next()?;
+    Some((challenge.to_string(), compatible))
+}
+
+fn cram_md5_response(challenge: &str, password: &str, compatible: bool) -> String {
+    let mut mac =
+        <Hmac<Md5>>::new_from_slice(password.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(challenge.as_bytes());
+    bareos_base64(&mac.finalize().into_bytes(), compatible)
+}
+
+// Bareos' own base64 variant: no padding, and without `compatible` bytes are sign-extended
+fn bareos_base64(data: &[u8], compatible: bool) -> String {
+    let mut output = String::new();
+    let mut reg: u32 = 0;
+    let mut rem: u32 = 0;
+    let mut bytes = data.iter();
+
+    loop {
+        if rem < 6 {
+            let Some(&byte) = bytes.next() else {
+                break;
+            };
+            reg <<= 8;
+            reg |= if compatible {
+                byte as u32
+            } else {
+                byte as i8 as i32 as u32
+            };
+            rem += 8;
+        }
+        output.push(BASE64_DIGITS[((reg >> (rem - 6)) & 0x3f) as usize] as char);
+        rem -= 6;
+    }
+
+    if rem > 0 {
+        let mask = (1 << rem) - 1;
+        let index = if compatible {
+            (reg & mask) << (6 - rem)
+        } else {
+            reg & mask
+        };
+        output.push(BASE64_DIGITS[index as usize] as char);
+    }
+
+    output
+}
+
+fn random_u32() -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to generate a challenge: {}", e))?;
+    Ok(u32::from_ne_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::{TcpListener, TcpStream};
+
+    const CHALLENGE: &str = "<1234.5678@bareos-dir>";
+
+    async fn write_message(stream: &mut TcpStream, message: &str) {
+        stream
+            .write_all(&(message.len() as i32).to_be_bytes())
+            .await
+            .unwrap();
+        stream.write_all(message.as_bytes()).await.unwrap();
+    }
+
+    async fn write_signal(stream: &mut TcpStream, signal: i32) {
+        stream.write_all(&signal.to_be_bytes()).await.unwrap();
+    }
+
+    // Skips signals such as heartbeat responses
+    async fn read_message(stream: &mut TcpStream) -> Option<String> {
+        let mut header = [0u8; 4];
+        let length = loop {
+            stream.read_exact(&mut header).await.ok()?;
+            match i32::from_be_bytes(header) {
+                BNET_HB_RESPONSE => continue,
+                length => break length,
+            }
+        };
+        let mut body = vec![0u8; length as usize];
+        stream.read_exact(&mut body).await.ok()?;
+        Some(String::from_utf8(body).unwrap())
+    }
+
+    // A Director that knows the console password "secret": it runs the Hello/CRAM-MD5
+    // handshake, answers `version`, and asks for input on `restore`
+    async fn stand_in_director() -> (u16, tokio::task::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let director = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let hello = read_message(&mut stream).await.unwrap();
+            assert_eq!(hello, "Hello *UserAgent* calling version 23.0.0\n");
+
+            write_message(
+                &mut stream,
+                &format!("auth cram-md5c {} ssl=0\n", CHALLENGE),
+            )
+            .await;
+            let response = read_message(&mut stream).await.unwrap();
+            // HMAC-MD5 keyed with the hex MD5 of "secret", in plain base64 without padding
+            if response != "TkNeFoRzP1opNXlrqXPlJQ" {
+                write_message(&mut stream, AUTH_FAILED).await;
+                return;
+            }
+            write_message(&mut stream, AUTH_OK).await;
+
+            let challenge = read_message(&mut stream).await.unwrap();
+            let (challenge, compatible) = parse_challenge(&challenge).unwrap();
+            assert!(compatible);
+            let password = format!("{:x}", Md5::digest(b"secret"));
+            let response = cram_md5_response(&challenge, &password, compatible);
+            write_message(&mut stream, &response).await;
+            assert_eq!(read_message(&mut stream).await.unwrap(), AUTH_OK);
+            write_message(&mut stream, "1000 OK: 103 bareos-dir Version: 23.0.0\n").await;
+
+            while let Some(command) = read_message(&mut stream).await {
+                match command.as_str() {
+                    "version" => {
+                        write_signal(&mut stream, BNET_HEARTBEAT).await;
+                        write_message(&mut stream, "bareos-dir Version: 23.0.0\n").await;
+                        write_signal(&mut stream, BNET_EOD).await;
+                    }
+                    "restore" => {
+                        write_message(&mut stream, "Select the client:\n").await;
+                        write_signal(&mut stream, BNET_TEXT_INPUT).await;
+                        assert_eq!(read_message(&mut stream).await.unwrap(), ".");
+                        write_message(&mut stream, "Selection aborted\n").await;
+                        write_signal(&mut stream, BNET_MAIN_PROMPT).await;
+                    }
+                    _ => write_signal(&mut stream, BNET_EOD).await,
+                }
+            }
+        });
+        (port, director)
+    }
+
+    #[tokio::test]
+    async fn authenticates_and_runs_commands_over_loopback() {
+        let (port, director) = stand_in_director().await;
+        let mut session = Director::new("127.0.0.1", port, "secret")
+            .connect()
+            .await
+            .unwrap();
+        assert_eq!(
+            session.execute("version").await.unwrap(),
+            "bareos-dir Version: 23.0.0\n"
+        );
+        let error = session.execute("restore").await.unwrap_err();
+        assert!(error.to_string().contains("asked for interactive input"));
+        assert!(error.downcast_ref::<NotSent>().is_none());
+        // The session is still in step with the Director after the refused command
+        assert_eq!(
+            session.execute("version").await.unwrap(),
+            "bareos-dir Version: 23.0.0\n"
+        );
+        drop(session);
+        director.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_a_rejected_password() {
+        let (port, director) = stand_in_director().await;
+        let error = Director::new("127.0.0.1", port, "wrong")
+            .connect()
+            .await
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("rejected the console password"));
+        director.await.unwrap();
+    }
+
+    #[test]
+    fn encodes_bareos_base64() {
+        assert_eq!(bareos_base64(b"abc", true), "YWJj");
+        assert_eq!(bareos_base64(b"ab", true), "YWI");
+        assert_eq!(bareos_base64(b"ab", false), "YWC");
+        assert_eq!(bareos_base64(&[0xff], true), "/w");
+        // Without `compatible` bytes above 0x7f are sign-extended
+        assert_eq!(bareos_base64(&[0xff], false), "/D");
+        assert_eq!(bareos_base64(b"", true), "");
+    }
+
+    #[test]
+    fn answers_cram_md5_challenges() {
+        // RFC 2104 test vector 2
+        let challenge = "what do ya want for nothing?";
+        assert_eq!(
+            cram_md5_response(challenge, "Jefe", true),
+            "dQx4PmqwtQPqqG4xCl23OA"
+        );
+        assert_eq!(
+            cram_md5_response(challenge, "Jefe", false),
+            "dQx4Pm+wtQ/qqG4xCl+3OA"
+        );
+        assert_eq!(
+            parse_challenge("auth cram-md5 <1.2@dir> ssl=0\n"),
+            Some(("<1.2@dir>".to_string(), false))
+        );
+        assert_eq!(parse_challenge("1999 Authorization failed.\n"), None);
+    }
+
+    #[test]
+    fn hashes_console_passwords() {
+        assert_eq!(hash_password("secret"), "5ebe2294ecd0e0f08eab7690d2a6ee69");
+        assert_eq!(
+            hash_password("[md5]5ebe2294ecd0e0f08eab7690d2a6ee69"),
+            "5ebe2294ecd0e0f08eab7690d2a6ee69"
+        );
+        let director = Director::new("localhost", DEFAULT_DIRECTOR_PORT, "secret");
+        assert_eq!(director.password, "5ebe2294ecd0e0f08eab7690d2a6ee69");
+    }
+}
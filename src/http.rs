@@ -0,0 +1,578 @@
+//! MCP Streamable HTTP transport: JSON-RPC over `POST /mcp`, with tool results optionally
+//! streamed as Server-Sent Events.
+
+use anyhow::{Context, Result};
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use futures_util::{stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tracing::info;
+
+use crate::auth::{Identity, Policy};
+use crate::confirm::random_token;
+use crate::server::Server;
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
+pub const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+pub const DEFAULT_MAX_SESSIONS: usize = 256;
+
+/// Settings for `serve`.
+pub struct HttpConfig {
+    pub bind: SocketAddr,
+    /// Browser origins allowed to call the server; requests from any other `Origin` are
+    /// refused to prevent DNS rebinding. Requests without an `Origin` header are allowed.
+    pub allowed_origins: Vec<String>,
+    pub max_concurrent: usize,
+    /// Sessions without a request for this long are forgotten; the client then gets a 404
+    /// and has to initialize again.
+    pub session_idle_timeout: Duration,
+    /// `initialize` is refused while this many sessions are open.
+    pub max_sessions: usize,
+    /// Bearer tokens and what each identity may access; without it every caller is
+    /// unrestricted.
+    pub policy: Option<Policy>,
+}
+
+struct HttpSession {
+    // Name of the identity that opened it
+    identity: String,
+    last_used: Instant,
+}
+
+struct HttpState {
+    server: Arc<Server>,
+    sessions: Mutex<HashMap<String, HttpSession>>,
+    session_idle_timeout: Duration,
+    max_sessions: usize,
+    allowed_origins: Vec<String>,
+    permits: Arc<Semaphore>,
+    policy: Option<Policy>,
+    anonymous: Arc<Identity>,
+}
+
+/// Serves MCP over HTTP on `config.bind` until the process is stopped.
+pub async fn serve(server: Arc<Server>, config: HttpConfig) -> Result<()> {
+    let bind = config.bind;
+    let listener = tokio::net::TcpListener::bind(bind)
+        .await
+        .with_context(|| format!("Failed to bind {}", bind))?;
+    info!("Listening for MCP over HTTP on http://{}/mcp", bind);
+    axum::serve(listener, router(server, config))
+        .await
+        .context("HTTP server failed")
+}
+
+fn router(server: Arc<Server>, config: HttpConfig) -> Router {
+    let state = Arc::new(HttpState {
+        server,
+        sessions: Mutex::new(HashMap::new()),
+        session_idle_timeout: config.session_idle_timeout,
+        max_sessions: config.max_sessions,
+        allowed_origins: config.allowed_origins,
+        permits: Arc::new(Semaphore::new(config.max_concurrent.max(1))),
+        policy: config.policy,
+        anonymous: Arc::new(Identity::unrestricted("anonymous")),
+    });
+
+    Router::new()
+        .route(
+            "/mcp",
+            post(handle_post).get(handle_get).delete(handle_delete),
+        )
+        .with_state(state)
+}
+
+fn json_rpc_error(status: StatusCode, code: i64, message: &str) -> Response {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": {
+            "code": code,
+            "message": message
+        }
+    });
+    (status, Json(body)).into_response()
+}
+
+fn origin_allowed(state: &HttpState, headers: &HeaderMap) -> bool {
+    match headers.get(header::ORIGIN).map(HeaderValue::to_str) {
+        None => true,
+        Some(Ok(origin)) => state
+            .allowed_origins
+            .iter()
+            .any(|allowed| allowed == origin),
+        Some(Err(_)) => false,
+    }
+}
+
+fn forbidden_origin() -> Response {
+    json_rpc_error(StatusCode::FORBIDDEN, -32600, "Origin not allowed")
+}
+
+fn authenticate(state: &HttpState, headers: &HeaderMap) -> Option<Arc<Identity>> {
+    let Some(policy) = &state.policy else {
+        return Some(Arc::clone(&state.anonymous));
+    };
+
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|authorization| policy.authenticate(authorization))
+}
+
+fn unauthorized() -> Response {
+    let mut response = json_rpc_error(
+        StatusCode::UNAUTHORIZED,
+        -32600,
+        "Missing or invalid bearer token",
+    );
+    response
+        .headers_mut()
+        .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    response
+}
+
+fn session_id(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+}
+
+impl HttpState {
+    // Marks `session` as used if `identity` opened it and it has not been idle too long
+    fn resume_session(&self, session: &str, identity: &Identity) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        match sessions.get_mut(session) {
+            Some(open) if now - open.last_used > self.session_idle_timeout => {
+                sessions.remove(session);
+                false
+            }
+            Some(open) if open.identity == identity.name => {
+                open.last_used = now;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // `None` while `max_sessions` sessions are open
+    fn open_session(&self, identity: &Identity) -> Result<Option<String>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        sessions.retain(|_, open| now - open.last_used <= self.session_idle_timeout);
+        if sessions.len() >= self.max_sessions {
+            return Ok(None);
+        }
+
+        let session = random_token()?;
+        sessions.insert(
+            session.clone(),
+            HttpSession {
+                identity: identity.name.clone(),
+                last_used: now,
+            },
+        );
+        Ok(Some(session))
+    }
+}
+
+// The client must accept both, but only gets a stream if it asked for one
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|accept| accept.contains("text/event-stream"))
+}
+
+async fn handle_post(
+    State(state): State<Arc<HttpState>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if !origin_allowed(&state, &headers) {
+        return forbidden_origin();
+    }
+    let Some(identity) = authenticate(&state, &headers) else {
+        return unauthorized();
+    };
+
+    let request: Value = match serde_json::from_str(&body) {
+        Ok(request) => request,
+        Err(e) => {
+            return json_rpc_error(
+                StatusCode::BAD_REQUEST,
+                -32700,
+                &format!("Parse error: {}", e),
+            )
+        }
+    };
+    if !request.is_object() {
+        return json_rpc_error(
+            StatusCode::BAD_REQUEST,
+            -32600,
+            "Expected a single JSON-RPC message",
+        );
+    }
+
+    // A session starts with `initialize`; every later message must carry its id and come
+    // from the same identity
+    let session = match session_id(&headers) {
+        Some(session) if state.resume_session(session, &identity) => session.to_string(),
+        Some(_) => {
+            return json_rpc_error(StatusCode::NOT_FOUND, -32600, "Session not found");
+        }
+        None if request["method"] == "initialize" => match state.open_session(&identity) {
+            Ok(Some(session)) => session,
+            Ok(None) => {
+                return json_rpc_error(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    -32600,
+                    "Too many open sessions; end unused ones with DELETE",
+                );
+            }
+            Err(e) => {
+                let message = e.to_string();
+                return json_rpc_error(StatusCode::INTERNAL_SERVER_ERROR, -32603, &message);
+            }
+        },
+        None => {
+            return json_rpc_error(
+                StatusCode::BAD_REQUEST,
+                -32600,
+                "Missing Mcp-Session-Id header",
+            )
+        }
+    };
+    let session_header = HeaderValue::from_str(&session).expect("session ids are hex");
+
+    // Notifications and responses are acknowledged without a body
+    if request.get("id").is_none() {
+        state
+            .server
+            .handle_session_request(&session, &identity, request)
+            .await;
+        return StatusCode::ACCEPTED.into_response();
+    }
+
+    // Waited for inside the server, so a queued tool call can still be cancelled
+    let permit = Arc::clone(&state.permits).acquire_owned();
+
+    // Tool calls can take minutes; streaming keeps proxies from timing the request out, and
+    // a client that disconnects drops the stream, which cancels the call
+    if request["method"] == "tools/call" && accepts_event_stream(&headers) {
+        let server = Arc::clone(&state.server);
+        let events = stream::once(async move {
+            server
+                .handle_queued_session_request(&session, &identity, request, permit)
+                .await
+                .map(|response| Event::default().event("message").json_data(response))
+        })
+        .filter_map(|event| async move { event });
+
+        let mut response = Sse::new(events)
+            .keep_alive(KeepAlive::default())
+            .into_response();
+        response
+            .headers_mut()
+            .insert(SESSION_HEADER, session_header);
+        return response;
+    }
+
+    let response = state
+        .server
+        .handle_queued_session_request(&session, &identity, request, permit)
+        .await;
+
+    let mut response = match response {
+        Some(response) => Json(response).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    };
+    response
+        .headers_mut()
+        .insert(SESSION_HEADER, session_header);
+    response
+}
+
+// No server-initiated messages are sent, so there is no stream to open
+async fn handle_get() -> StatusCode {
+    StatusCode::METHOD_NOT_ALLOWED
+}
+
+async fn handle_delete(State(state): State<Arc<HttpState>>, headers: HeaderMap) -> Response {
+    if !origin_allowed(&state, &headers) {
+        return forbidden_origin();
+    }
+    let Some(identity) = authenticate(&state, &headers) else {
+        return unauthorized();
+    };
+
+    match session_id(&headers) {
+        Some(session) if state.resume_session(session, &identity) => {
+            state.sessions.lock().unwrap().remove(session);
+            StatusCode::OK.into_response()
+        }
+        _ => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBconsole;
+    use axum::body::Body;
+    use axum::http::{Method, Request};
+    use tower::ServiceExt;
+
+    const OPS_TOKEN: &str = "ops-0123456789abcdef";
+    const WEB_TOKEN: &str = "web-0123456789abcdef";
+
+    fn config(policy: Option<Policy>) -> HttpConfig {
+        HttpConfig {
+            bind: "127.0.0.1:0".parse().unwrap(),
+            allowed_origins: vec!["https://chat.example.com".to_string()],
+            max_concurrent: 2,
+            session_idle_timeout: DEFAULT_SESSION_IDLE_TIMEOUT,
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            policy,
+        }
+    }
+
+    fn app(fake: &FakeBconsole, config: HttpConfig) -> Router {
+        router(Arc::new(Server::new(fake.client())), config)
+    }
+
+    fn policy() -> Policy {
+        let path = std::env::temp_dir().join(format!(
+            "bareos-mcp-policy-{}-{:?}.json",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        std::fs::write(
+            &path,
+            json!({
+                "identities": {
+                    "ops": { "token": OPS_TOKEN },
+                    "web-team": { "token": WEB_TOKEN, "clients": ["web*"] }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let policy = Policy::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        policy
+    }
+
+    fn request(method: Method, headers: &[(&str, &str)], body: Value) -> Request<Body> {
+        let mut request = Request::builder().method(method).uri("/mcp");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(Body::from(body.to_string())).unwrap()
+    }
+
+    fn message(id: u64, method: &str) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": {} })
+    }
+
+    async fn send(app: &Router, request: Request<Body>) -> Response {
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    async fn initialize(app: &Router, headers: &[(&str, &str)]) -> String {
+        let response = send(
+            app,
+            request(Method::POST, headers, message(1, "initialize")),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        response.headers()[SESSION_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn refuses_origins_that_are_not_allowed() {
+        let fake = FakeBconsole::new("");
+        let app = app(&fake, config(None));
+
+        for method in [Method::POST, Method::DELETE] {
+            let response = send(
+                &app,
+                request(
+                    method,
+                    &[("origin", "https://evil.example")],
+                    message(1, "initialize"),
+                ),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        initialize(&app, &[("origin", "https://chat.example.com")]).await;
+        initialize(&app, &[]).await;
+    }
+
+    #[tokio::test]
+    async fn requires_the_session_id_after_initialize() {
+        let fake = FakeBconsole::new("");
+        let app = app(&fake, config(None));
+
+        let response = send(&app, request(Method::POST, &[], message(2, "tools/list"))).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = send(
+            &app,
+            request(
+                Method::POST,
+                &[(SESSION_HEADER, "unknown")],
+                message(2, "tools/list"),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let session = initialize(&app, &[]).await;
+        let response = send(
+            &app,
+            request(
+                Method::POST,
+                &[(SESSION_HEADER, &session)],
+                message(2, "tools/list"),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[SESSION_HEADER], session.as_str());
+    }
+
+    #[tokio::test]
+    async fn sessions_belong_to_the_identity_that_opened_them() {
+        let fake = FakeBconsole::new("");
+        let app = app(&fake, config(Some(policy())));
+        let ops = format!("Bearer {}", OPS_TOKEN);
+        let web = format!("Bearer {}", WEB_TOKEN);
+
+        let response = send(&app, request(Method::POST, &[], message(1, "initialize"))).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let session = initialize(&app, &[("authorization", &ops)]).await;
+        for method in [Method::POST, Method::DELETE] {
+            let response = send(
+                &app,
+                request(
+                    method,
+                    &[("authorization", &web), (SESSION_HEADER, &session)],
+                    message(2, "tools/list"),
+                ),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+        let response = send(
+            &app,
+            request(
+                Method::POST,
+                &[("authorization", &ops), (SESSION_HEADER, &session)],
+                message(2, "tools/list"),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn get_is_not_allowed() {
+        let fake = FakeBconsole::new("");
+        let app = app(&fake, config(None));
+        let session = initialize(&app, &[]).await;
+
+        let response = send(
+            &app,
+            request(Method::GET, &[(SESSION_HEADER, &session)], json!(null)),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn delete_ends_the_session() {
+        let fake = FakeBconsole::new("");
+        let app = app(&fake, config(None));
+        let session = initialize(&app, &[]).await;
+
+        let delete = || request(Method::DELETE, &[(SESSION_HEADER, &session)], json!(null));
+        assert_eq!(send(&app, delete()).await.status(), StatusCode::OK);
+        assert_eq!(send(&app, delete()).await.status(), StatusCode::NOT_FOUND);
+        let response = send(
+            &app,
+            request(
+                Method::POST,
+                &[(SESSION_HEADER, &session)],
+                message(2, "tools/list"),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn limits_the_number_of_sessions() {
+        let fake = FakeBconsole::new("");
+        let app = app(
+            &fake,
+            HttpConfig {
+                max_sessions: 2,
+                ..config(None)
+            },
+        );
+
+        let first = initialize(&app, &[]).await;
+        initialize(&app, &[]).await;
+        let response = send(&app, request(Method::POST, &[], message(1, "initialize"))).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let delete = request(Method::DELETE, &[(SESSION_HEADER, &first)], json!(null));
+        assert_eq!(send(&app, delete).await.status(), StatusCode::OK);
+        initialize(&app, &[]).await;
+    }
+
+    #[tokio::test]
+    async fn forgets_idle_sessions() {
+        let fake = FakeBconsole::new("");
+        let app = app(
+            &fake,
+            HttpConfig {
+                session_idle_timeout: Duration::from_millis(50),
+                max_sessions: 1,
+                ..config(None)
+            },
+        );
+
+        let idle = initialize(&app, &[]).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // The idle session no longer counts towards the limit
+        initialize(&app, &[]).await;
+        let response = send(
+            &app,
+            request(
+                Method::POST,
+                &[(SESSION_HEADER, &idle)],
+                message(2, "tools/list"),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
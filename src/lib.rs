@@ -0,0 +1,27 @@
+//! Read-only access to a Bareos Director, plus the MCP tool registry and JSON-RPC
+//! dispatcher built on top of it.
+
+pub mod auth;
+pub mod bareos;
+pub mod bvfs;
+pub mod chain;
+pub mod completion;
+pub mod confirm;
+pub mod director;
+pub mod http;
+pub mod model;
+pub mod policy;
+pub mod prompts;
+pub mod redact;
+pub mod resources;
+pub mod server;
+mod session;
+pub mod status;
+pub mod stdio;
+#[cfg(test)]
+mod testing;
+pub mod tools;
+pub mod validate;
+
+pub use bareos::{BareosClient, JobListParams};
+pub use server::Server;
@@ -0,0 +1,814 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Map, Value};
+
+// Status, type and level are single-letter codes in the catalog
+macro_rules! code_enum {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident => $code:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum $name {
+            $($variant,)*
+            Other(char),
+        }
+
+        impl $name {
+            pub fn from_code(code: char) -> Self {
+                match code {
+                    $($code => $name::$variant,)*
+                    other => $name::Other(other),
+                }
+            }
+
+            pub fn code(&self) -> char {
+                match self {
+                    $($name::$variant => $code,)*
+                    $name::Other(code) => *code,
+                }
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.code().to_string())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let code = String::deserialize(deserializer)?;
+                Ok($name::from_code(code.chars().next().unwrap_or(' ')))
+            }
+        }
+    };
+}
+
+code_enum!(
+    /// Job status as stored in the catalog's `JobStatus` column.
+    JobStatus {
+        Created => 'C',
+        Running => 'R',
+        Blocked => 'B',
+        Terminated => 'T',
+        Warnings => 'W',
+        Error => 'E',
+        NonFatalError => 'e',
+        FatalError => 'f',
+        Differences => 'D',
+        Canceled => 'A',
+        Incomplete => 'I',
+        Committing => 'L',
+        WaitingForClient => 'F',
+        WaitingForStorage => 'S',
+        WaitingForMedia => 'm',
+        WaitingForMount => 'M',
+        WaitingForStorageResource => 's',
+        WaitingForJobResource => 'j',
+        WaitingForClientResource => 'c',
+        WaitingForMaxJobs => 'd',
+        WaitingForStartTime => 't',
+        WaitingForPriority => 'p',
+        DespoolingAttributes => 'a',
+        BatchInsert => 'i',
+        DespoolingData => 'l',
+        Queued => 'q',
+    }
+);
+
+impl JobStatus {
+    /// Whether the job has ended and will not change status again.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Terminated
+                | JobStatus::Warnings
+                | JobStatus::Error
+                | JobStatus::NonFatalError
+                | JobStatus::FatalError
+                | JobStatus::Differences
+                | JobStatus::Canceled
+                | JobStatus::Incomplete
+        )
+    }
+}
+
+code_enum!(
+    /// Job type as stored in the catalog's `Type` column.
+    JobType {
+        Backup => 'B',
+        MigratedJob => 'M',
+        Verify => 'V',
+        Restore => 'R',
+        Console => 'U',
+        Internal => 'I',
+        Admin => 'D',
+        Archive => 'A',
+        CopiedJob => 'C',
+        Copy => 'c',
+        Migration => 'g',
+        Scan => 'S',
+        Consolidate => 'O',
+    }
+);
+
+code_enum!(
+    /// Job level as stored in the catalog's `Level` column.
+    JobLevel {
+        Full => 'F',
+        Incremental => 'I',
+        Differential => 'D',
+        VirtualFull => 'f',
+        Since => 'S',
+        VerifyCatalog => 'C',
+        VerifyInit => 'V',
+        VerifyVolumeToCatalog => 'O',
+        VerifyDiskToCatalog => 'd',
+        VerifyData => 'A',
+        Base => 'B',
+        None => ' ',
+    }
+);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    #[serde(rename = "jobid", deserialize_with = "number")]
+    pub job_id: u64,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job: Option<String>,
+    #[serde(default, alias = "clientname", skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    #[serde(rename = "type")]
+    pub job_type: JobType,
+    pub level: JobLevel,
+    #[serde(rename = "jobstatus")]
+    pub status: JobStatus,
+    #[serde(
+        rename = "schedtime",
+        default,
+        deserialize_with = "text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sched_time: Option<String>,
+    #[serde(
+        rename = "starttime",
+        default,
+        deserialize_with = "text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub start_time: Option<String>,
+    #[serde(
+        rename = "endtime",
+        default,
+        deserialize_with = "text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub end_time: Option<String>,
+    #[serde(
+        rename = "jobtdate",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub job_tdate: Option<u64>,
+    #[serde(
+        rename = "jobfiles",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub files: Option<u64>,
+    #[serde(
+        rename = "jobbytes",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub bytes: Option<u64>,
+    #[serde(
+        rename = "joberrors",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub errors: Option<u64>,
+    #[serde(
+        rename = "clientid",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub client_id: Option<u64>,
+    #[serde(
+        rename = "filesetid",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub fileset_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fileset: Option<String>,
+    #[serde(
+        rename = "poolname",
+        default,
+        alias = "pool",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub pool: Option<String>,
+    #[serde(
+        rename = "priorjobid",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub prior_job_id: Option<u64>,
+    /// Whether the job's file records were pruned from the catalog.
+    #[serde(
+        rename = "purgedfiles",
+        default,
+        deserialize_with = "optional_flag",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub purged_files: Option<bool>,
+}
+
+impl Job {
+    pub fn schema() -> Value {
+        object_schema(
+            &[
+                ("jobid", "integer"),
+                ("name", "string"),
+                ("job", "string"),
+                ("client", "string"),
+                ("schedtime", "string"),
+                ("starttime", "string"),
+                ("endtime", "string"),
+                ("jobtdate", "integer"),
+                ("jobfiles", "integer"),
+                ("jobbytes", "integer"),
+                ("joberrors", "integer"),
+                ("clientid", "integer"),
+                ("filesetid", "integer"),
+                ("fileset", "string"),
+                ("poolname", "string"),
+                ("priorjobid", "integer"),
+                ("purgedfiles", "boolean"),
+                ("type", "string"),
+                ("level", "string"),
+                ("jobstatus", "string"),
+            ],
+            &["jobid", "name", "type", "level", "jobstatus"],
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Volume {
+    #[serde(rename = "mediaid", deserialize_with = "number")]
+    pub media_id: u64,
+    #[serde(rename = "volumename")]
+    pub name: String,
+    #[serde(rename = "volstatus")]
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "optional_flag",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub enabled: Option<bool>,
+    #[serde(
+        rename = "volbytes",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub bytes: Option<u64>,
+    #[serde(
+        rename = "maxvolbytes",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_bytes: Option<u64>,
+    #[serde(
+        rename = "volfiles",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub files: Option<u64>,
+    #[serde(
+        rename = "voljobs",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub jobs: Option<u64>,
+    #[serde(
+        rename = "volretention",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub retention: Option<u64>,
+    #[serde(
+        default,
+        deserialize_with = "optional_flag",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recycle: Option<bool>,
+    #[serde(
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub slot: Option<u64>,
+    #[serde(
+        rename = "inchanger",
+        default,
+        deserialize_with = "optional_flag",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub in_changer: Option<bool>,
+    #[serde(rename = "mediatype", default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<String>,
+    #[serde(
+        rename = "lastwritten",
+        default,
+        deserialize_with = "text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_written: Option<String>,
+}
+
+impl Volume {
+    pub fn schema() -> Value {
+        object_schema(
+            &[
+                ("mediaid", "integer"),
+                ("volumename", "string"),
+                ("volstatus", "string"),
+                ("pool", "string"),
+                ("enabled", "boolean"),
+                ("volbytes", "integer"),
+                ("maxvolbytes", "integer"),
+                ("volfiles", "integer"),
+                ("voljobs", "integer"),
+                ("volretention", "integer"),
+                ("recycle", "boolean"),
+                ("slot", "integer"),
+                ("inchanger", "boolean"),
+                ("mediatype", "string"),
+                ("storage", "string"),
+                ("lastwritten", "string"),
+            ],
+            &["mediaid", "volumename", "volstatus"],
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pool {
+    #[serde(rename = "poolid", deserialize_with = "number")]
+    pub pool_id: u64,
+    pub name: String,
+    #[serde(
+        rename = "numvols",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub volumes: Option<u64>,
+    #[serde(
+        rename = "maxvols",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_volumes: Option<u64>,
+    #[serde(rename = "pooltype", default, skip_serializing_if = "Option::is_none")]
+    pub pool_type: Option<String>,
+    #[serde(
+        rename = "labelformat",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub label_format: Option<String>,
+}
+
+impl Pool {
+    pub fn schema() -> Value {
+        object_schema(
+            &[
+                ("poolid", "integer"),
+                ("name", "string"),
+                ("numvols", "integer"),
+                ("maxvols", "integer"),
+                ("pooltype", "string"),
+                ("labelformat", "string"),
+            ],
+            &["poolid", "name"],
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Client {
+    #[serde(rename = "clientid", deserialize_with = "number")]
+    pub client_id: u64,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uname: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "optional_flag",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub autoprune: Option<bool>,
+    #[serde(
+        rename = "fileretention",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub file_retention: Option<u64>,
+    #[serde(
+        rename = "jobretention",
+        default,
+        deserialize_with = "optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub job_retention: Option<u64>,
+}
+
+impl Client {
+    pub fn schema() -> Value {
+        object_schema(
+            &[
+                ("clientid", "integer"),
+                ("name", "string"),
+                ("uname", "string"),
+                ("autoprune", "boolean"),
+                ("fileretention", "integer"),
+                ("jobretention", "integer"),
+            ],
+            &["clientid", "name"],
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSet {
+    #[serde(rename = "filesetid", deserialize_with = "number")]
+    pub fileset_id: u64,
+    #[serde(rename = "fileset")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub md5: Option<String>,
+    #[serde(
+        rename = "createtime",
+        default,
+        deserialize_with = "text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub create_time: Option<String>,
+}
+
+impl FileSet {
+    pub fn schema() -> Value {
+        object_schema(
+            &[
+                ("filesetid", "integer"),
+                ("fileset", "string"),
+                ("md5", "string"),
+                ("createtime", "string"),
+            ],
+            &["filesetid", "fileset"],
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobLogEntry {
+    #[serde(
+        default,
+        deserialize_with = "text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub time: Option<String>,
+    #[serde(rename = "logtext")]
+    pub text: String,
+}
+
+impl JobLogEntry {
+    pub fn schema() -> Value {
+        object_schema(&[("time", "string"), ("logtext", "string")], &["logtext"])
+    }
+
+    /// Parses the plain text `list joblog` output, one entry per line.
+    pub fn from_text(output: &str) -> Vec<JobLogEntry> {
+        output
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let line = line.trim_start();
+                // Lines start with "YYYY-MM-DD HH:MM:SS" when the entry carries a timestamp
+                let is_timestamp = line.len() > 19
+                    && line.as_bytes()[4] == b'-'
+                    && line.as_bytes()[10] == b' '
+                    && line.as_bytes()[13] == b':';
+                if is_timestamp && line.is_char_boundary(19) {
+                    JobLogEntry {
+                        time: Some(line[..19].to_string()),
+                        text: line[19..].trim_start().to_string(),
+                    }
+                } else {
+                    JobLogEntry {
+                        time: None,
+                        text: line.to_string(),
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub filename: String,
+}
+
+impl FileEntry {
+    pub fn schema() -> Value {
+        object_schema(&[("filename", "string")], &["filename"])
+    }
+}
+
+/// Result row of `list jobmedia`: a volume holding (part of) a job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobMedia {
+    #[serde(rename = "jobid", deserialize_with = "number")]
+    pub job_id: u64,
+    #[serde(rename = "volumename")]
+    pub volume: String,
+}
+
+/// Result row of `list jobs count`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobCount {
+    #[serde(deserialize_with = "number")]
+    pub count: u64,
+}
+
+/// Deserializes records from an `.api 2` result array.
+pub fn from_api<T: DeserializeOwned>(records: &Value) -> Result<Vec<T>> {
+    match records {
+        Value::Null => Ok(Vec::new()),
+        records => Vec::<T>::deserialize(records).context("Unexpected record in bconsole output"),
+    }
+}
+
+/// Deserializes records from bconsole's `+----+` table output.
+pub fn from_table<T: DeserializeOwned>(output: &str) -> Result<Vec<T>> {
+    let mut rows = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with('|'))
+        .map(|line| {
+            line.trim_matches('|')
+                .split('|')
+                .map(|cell| cell.trim().to_string())
+                .collect::<Vec<_>>()
+        });
+
+    let Some(header) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let header: Vec<String> = header.iter().map(|name| name.to_lowercase()).collect();
+
+    rows.map(|row| {
+        let record: Map<String, Value> = header
+            .iter()
+            .cloned()
+            .zip(row.into_iter().map(Value::String))
+            .collect();
+        T::deserialize(Value::Object(record)).context("Unexpected row in bconsole table")
+    })
+    .collect()
+}
+
+fn object_schema(properties: &[(&str, &str)], required: &[&str]) -> Value {
+    let properties: Map<String, Value> = properties
+        .iter()
+        .map(|(name, kind)| (name.to_string(), json!({ "type": kind })))
+        .collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required
+    })
+}
+
+fn parse_number(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(number) => number.as_u64(),
+        // Table output uses thousands separators
+        Value::String(text) => text.replace(',', "").trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    parse_number(&value)
+        .ok_or_else(|| serde::de::Error::custom(format!("expected a number, got {}", value)))
+}
+
+fn optional_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    Ok(parse_number(&Value::deserialize(deserializer)?))
+}
+
+fn optional_flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Bool(flag) => Some(flag),
+        value => parse_number(&value).map(|number| number != 0),
+    })
+}
+
+fn text<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(text) if !text.is_empty() => Some(text),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The subset of JSON Schema `object_schema` produces: every serialized field must be
+    // declared with a matching type, and every required field present
+    fn assert_matches_schema(value: &Value, schema: &Value) {
+        let object = value.as_object().unwrap();
+        for required in schema["required"].as_array().unwrap() {
+            let required = required.as_str().unwrap();
+            assert!(object.contains_key(required), "{} is missing", required);
+        }
+        for (key, field) in object {
+            let kind = schema["properties"][key]["type"]
+                .as_str()
+                .unwrap_or_else(|| panic!("{} is not in the schema", key));
+            let matches = match kind {
+                "integer" => field.is_u64(),
+                "string" => field.is_string(),
+                "boolean" => field.is_boolean(),
+                _ => false,
+            };
+            assert!(matches, "{} is {} but the schema says {}", key, field, kind);
+        }
+    }
+
+    fn roundtrip<T: DeserializeOwned + Serialize>(record: Value, schema: Value) -> T {
+        let mut records: Vec<T> = from_api(&json!([record])).unwrap();
+        assert_matches_schema(&serde_json::to_value(&records[0]).unwrap(), &schema);
+        records.remove(0)
+    }
+
+    #[test]
+    fn serializes_every_type_as_its_schema_says() {
+        let job: Job = roundtrip(
+            json!({
+                "jobid": "42", "job": "backup-web.2024-03-01_01.00.00_05", "name": "backup-web",
+                "clientname": "web01-fd", "type": "B", "level": "I", "jobstatus": "T",
+                "schedtime": "2024-03-01 01:00:00", "starttime": "2024-03-01 01:00:02",
+                "endtime": "2024-03-01 01:03:10", "jobtdate": "1709254802", "jobfiles": "1523",
+                "jobbytes": "104857600", "joberrors": "0", "clientid": "3", "filesetid": "2",
+                "fileset": "LinuxAll", "poolname": "Incremental", "priorjobid": "0",
+                "purgedfiles": "1"
+            }),
+            Job::schema(),
+        );
+        assert_eq!(job.client.as_deref(), Some("web01-fd"));
+        assert_eq!(job.purged_files, Some(true));
+
+        roundtrip::<Volume>(
+            json!({
+                "mediaid": 7, "volumename": "Full-0007", "volstatus": "Full", "pool": "Full",
+                "enabled": 1, "volbytes": "53687091200", "maxvolbytes": 0, "volfiles": 12,
+                "voljobs": 4, "volretention": 31536000, "recycle": 1, "slot": 0,
+                "inchanger": 0, "mediatype": "File", "storage": "File",
+                "lastwritten": "2024-03-01 01:03:10"
+            }),
+            Volume::schema(),
+        );
+        roundtrip::<Pool>(
+            json!({
+                "poolid": 1, "name": "Full", "numvols": 12, "maxvols": 100,
+                "pooltype": "Backup", "labelformat": "Full-"
+            }),
+            Pool::schema(),
+        );
+        roundtrip::<Client>(
+            json!({
+                "clientid": 3, "name": "web01-fd", "uname": "23.0.0 (Linux)", "autoprune": 1,
+                "fileretention": 5184000, "jobretention": 15552000
+            }),
+            Client::schema(),
+        );
+        roundtrip::<FileSet>(
+            json!({
+                "filesetid": 2, "fileset": "LinuxAll", "md5": "+CzzJ9/jkgkUdyv/5hRBgD",
+                "createtime": "2024-01-01 00:00:00"
+            }),
+            FileSet::schema(),
+        );
+        roundtrip::<JobLogEntry>(
+            json!({ "time": "2024-03-01 01:00:02", "logtext": "Start Backup JobId 42" }),
+            JobLogEntry::schema(),
+        );
+        roundtrip::<FileEntry>(json!({ "filename": "/etc/hosts" }), FileEntry::schema());
+    }
+
+    #[test]
+    fn reads_api_records() {
+        assert!(from_api::<Job>(&Value::Null).unwrap().is_empty());
+        let media: Vec<JobMedia> =
+            from_api(&json!([{ "jobid": "42", "volumename": "Incr-0003", "firstindex": 1 }]))
+                .unwrap();
+        assert_eq!(
+            (media[0].job_id, media[0].volume.as_str()),
+            (42, "Incr-0003")
+        );
+        assert!(from_api::<Job>(&json!([{ "jobid": "x", "name": "a" }])).is_err());
+    }
+
+    #[test]
+    fn reads_tables_with_thousands_separators() {
+        let output = "\
++-------+------------+-----------+------+-------+----------+-------------+-----------+
+| jobid | name       | client    | type | level | jobfiles | jobbytes    | jobstatus |
++-------+------------+-----------+------+-------+----------+-------------+-----------+
+| 1,042 | backup-web | web01-fd  | B    | F     | 12,345   | 104,857,600 | T         |
+| 1,043 | restore    | web01-fd  | R    |       | 0        | 0           | f         |
++-------+------------+-----------+------+-------+----------+-------------+-----------+
+";
+        let jobs: Vec<Job> = from_table(output).unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].job_id, 1042);
+        assert_eq!(jobs[0].files, Some(12_345));
+        assert_eq!(jobs[0].bytes, Some(104_857_600));
+        assert_eq!(jobs[1].level, JobLevel::None);
+        assert_eq!(jobs[1].status, JobStatus::FatalError);
+
+        let counts: Vec<JobCount> =
+            from_table("+-------+\n| count |\n+-------+\n| 1,234 |\n+-------+\n").unwrap();
+        assert_eq!(counts[0].count, 1234);
+        assert!(from_table::<Job>("No results to list.\n")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn maps_single_letter_codes() {
+        for code in ['T', 'W', 'f', 'A', 'R', 'q'] {
+            assert_eq!(JobStatus::from_code(code).code(), code);
+        }
+        assert_eq!(JobStatus::from_code('W'), JobStatus::Warnings);
+        assert_eq!(JobStatus::from_code('Z'), JobStatus::Other('Z'));
+        assert!(JobStatus::Canceled.is_final());
+        assert!(!JobStatus::Running.is_final());
+        assert_eq!(JobType::from_code('c'), JobType::Copy);
+        assert_eq!(JobType::from_code('C'), JobType::CopiedJob);
+        assert_eq!(JobLevel::from_code('f'), JobLevel::VirtualFull);
+        assert_eq!(JobLevel::from_code(' '), JobLevel::None);
+
+        let level: JobLevel = serde_json::from_value(json!("Incremental")).unwrap();
+        assert_eq!(level, JobLevel::Incremental);
+        let level: JobLevel = serde_json::from_value(json!("")).unwrap();
+        assert_eq!(level, JobLevel::None);
+        assert_eq!(serde_json::to_value(JobType::Backup).unwrap(), json!("B"));
+    }
+
+    #[test]
+    fn parses_text_job_logs() {
+        let entries = JobLogEntry::from_text(
+            " 2024-03-01 01:00:02 bareos-dir JobId 42: Start Backup JobId 42\n\n\
+             bareos-dir JobId 42: continued line\n",
+        );
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].time.as_deref(), Some("2024-03-01 01:00:02"));
+        assert_eq!(
+            entries[0].text,
+            "bareos-dir JobId 42: Start Backup JobId 42"
+        );
+        assert_eq!(entries[1].time, None);
+        assert_eq!(entries[1].text, "bareos-dir JobId 42: continued line");
+    }
+}
@@ -0,0 +1,254 @@
+use std::fmt;
+
+// Matched exactly: the Director also accepts abbreviations ("del" for "delete"), which are
+// refused here because they never match an entry below
+const READ_ONLY_COMMANDS: &[&str] = &[
+    "list",
+    "llist",
+    "show",
+    "status",
+    "version",
+    ".api",
+    // Only fill the BVFS cache tables, which the catalog keeps for browsing anyway
+    ".bvfs_get_jobids",
+    ".bvfs_lsdirs",
+    ".bvfs_lsfiles",
+    ".bvfs_update",
+    ".bvfs_versions",
+    ".clients",
+    ".consoles",
+    ".defaults",
+    ".filesets",
+    ".jobdefs",
+    ".jobs",
+    ".jobstatus",
+    ".levels",
+    ".media",
+    ".mediatypes",
+    ".pools",
+    ".profiles",
+    ".schedule",
+    ".storages",
+    ".types",
+    ".volstatus",
+];
+
+// Only allowed in write mode. `update` is further limited to volume status changes.
+const MUTATING_COMMANDS: &[&str] = &[
+    "run",
+    "rerun",
+    "cancel",
+    "enable",
+    "disable",
+    "mount",
+    "unmount",
+    "update",
+    "restore",
+    // Build and drop the selection table of a BVFS restore
+    ".bvfs_restore",
+    ".bvfs_cleanup",
+];
+
+/// A command that the policy does not allow to be sent to the Director.
+#[derive(Debug)]
+pub struct CommandRefused {
+    pub command: String,
+    pub reason: String,
+}
+
+impl fmt::Display for CommandRefused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Refusing to send {:?} to the Director: {}",
+            self.command, self.reason
+        )
+    }
+}
+
+impl std::error::Error for CommandRefused {}
+
+fn refuse(command: &str, reason: impl Into<String>) -> CommandRefused {
+    CommandRefused {
+        command: command.to_string(),
+        reason: reason.into(),
+    }
+}
+
+// Splits on whitespace outside of double quotes, like the Director's argument parser
+fn arguments(command: &str) -> impl Iterator<Item = String> + '_ {
+    let mut quoted = false;
+    command
+        .split(move |c: char| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            c.is_whitespace() && !quoted
+        })
+        .filter(|argument| !argument.is_empty())
+        .map(str::to_string)
+}
+
+fn verb(command: &str) -> Result<String, CommandRefused> {
+    // A newline would let a second command ride along with the first
+    if command.chars().any(char::is_control) {
+        return Err(refuse(command, "contains control characters"));
+    }
+
+    arguments(command)
+        .next()
+        .map(|verb| verb.to_ascii_lowercase())
+        .ok_or_else(|| refuse(command, "empty command"))
+}
+
+/// Accepts `command` only if it is a single read-only bconsole command.
+pub fn check_read_only(command: &str) -> Result<(), CommandRefused> {
+    let verb = verb(command)?;
+
+    if READ_ONLY_COMMANDS.contains(&verb.as_str()) {
+        Ok(())
+    } else {
+        Err(refuse(
+            command,
+            format!("'{}' is not an allowed read-only command", verb),
+        ))
+    }
+}
+
+/// Accepts `command` only if it is a single command from the write-mode allowlist.
+pub fn check_mutating(command: &str) -> Result<(), CommandRefused> {
+    let verb = verb(command)?;
+
+    if !MUTATING_COMMANDS.contains(&verb.as_str()) {
+        return Err(refuse(
+            command,
+            format!("'{}' is not an allowed write command", verb),
+        ));
+    }
+
+    // Without `where=` the Director falls back to the restore job's default, which may be
+    // the original location
+    if verb == "restore"
+        && !arguments(command).any(|argument| argument.to_ascii_lowercase().starts_with("where="))
+    {
+        return Err(refuse(command, "restore needs an explicit where="));
+    }
+
+    if verb == "update" {
+        let keys: Vec<String> = arguments(command)
+            .skip(1)
+            .map(|argument| {
+                argument
+                    .split('=')
+                    .next()
+                    .unwrap_or("")
+                    .to_ascii_lowercase()
+            })
+            .collect();
+        if keys.len() != 2
+            || !keys.contains(&"volume".to_string())
+            || !keys.contains(&"volstatus".to_string())
+        {
+            return Err(refuse(command, "only volume status updates are allowed"));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_read_only_commands() {
+        for command in [
+            "list jobs days=7",
+            "llist jobid=12",
+            "show job=\"backup web\"",
+            "status director",
+            "version",
+            ".api 2",
+            ".jobs type=B",
+            "LIST pools",
+        ] {
+            assert!(check_read_only(command).is_ok(), "{}", command);
+        }
+    }
+
+    #[test]
+    fn refuses_mutating_commands() {
+        for command in [
+            "delete volume=Full-0001 yes",
+            "purge volume=Full-0001",
+            "prune jobs client=web yes",
+            "run job=backup-web yes",
+            "cancel jobid=1",
+            "update volume=Full-0001 volstatus=Used",
+            "label volume=x pool=Full",
+            "restore client=web all done",
+            "messages",
+            ".sql query=\"DELETE FROM Job\"",
+            "@exec \"rm -rf /\"",
+            "@output /etc/passwd",
+        ] {
+            assert!(check_read_only(command).is_err(), "{}", command);
+        }
+    }
+
+    #[test]
+    fn refuses_abbreviations() {
+        assert!(check_read_only("del volume=x").is_err());
+        assert!(check_read_only("lis jobs").is_err());
+    }
+
+    #[test]
+    fn refuses_smuggled_commands() {
+        assert!(check_read_only("list jobid=1\ndelete volume=x yes").is_err());
+        assert!(check_read_only("list jobid=1\rdelete volume=x").is_err());
+        assert!(check_read_only("list jobid=1\0").is_err());
+    }
+
+    #[test]
+    fn allows_write_commands_only_through_check_mutating() {
+        for command in [
+            "run job=backup-web level=Full yes",
+            "rerun jobid=12 yes",
+            "cancel jobid=12 yes",
+            "enable job=backup-web",
+            "disable job=backup-web",
+            "mount storage=File slot=3",
+            "unmount storage=File",
+            "update volume=Full-0001 volstatus=Used",
+            "update volume=\"Tape 1\" volstatus=Read-Only",
+            "restore client=web01-fd where=/tmp/bareos-restores replace=never current file=\"/etc/hosts\" yes",
+            ".bvfs_restore path=b2abc jobid=1,2 fileid=3",
+            ".bvfs_cleanup path=b2abc",
+        ] {
+            assert!(check_mutating(command).is_ok(), "{}", command);
+            assert!(check_read_only(command).is_err(), "{}", command);
+        }
+    }
+
+    #[test]
+    fn refuses_other_commands_in_write_mode() {
+        for command in [
+            "delete volume=Full-0001 yes",
+            "purge volume=Full-0001",
+            "update pool=Full",
+            "update volume=Full-0001 pool=Scratch",
+            "update volume=Full-0001 volstatus=Used pool=Scratch",
+            "run job=backup-web yes\ndelete volume=x yes",
+            "restore client=web01-fd current file=/etc/hosts yes",
+            "list jobs",
+        ] {
+            assert!(check_mutating(command).is_err(), "{}", command);
+        }
+    }
+
+    #[test]
+    fn refuses_empty_commands() {
+        assert!(check_read_only("").is_err());
+        assert!(check_read_only("   ").is_err());
+    }
+}
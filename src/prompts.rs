@@ -0,0 +1,211 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::validate::{Arguments, InvalidParams};
+
+fn argument(name: &str, description: &str, required: bool) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "required": required
+    })
+}
+
+/// Prompt templates as advertised by `prompts/list`.
+pub fn definitions() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "daily_backup_review",
+            "title": "Daily backup review",
+            "description": "Review the backups of the last 24 hours, explain every failure and group the results by client.",
+            "arguments": [
+                argument("client", "Only review this client", false)
+            ]
+        }),
+        json!({
+            "name": "investigate_failed_job",
+            "title": "Investigate failed job",
+            "description": "Find out why a job failed from its status and log, and suggest how to fix it.",
+            "arguments": [
+                argument("jobid", "ID of the failed job", true)
+            ]
+        }),
+        json!({
+            "name": "volume_capacity_review",
+            "title": "Volume capacity review",
+            "description": "Review volume usage and status to find out how much capacity is left and which volumes can be recycled.",
+            "arguments": [
+                argument("pool", "Only review this pool", false)
+            ]
+        }),
+        json!({
+            "name": "restore_planning",
+            "title": "Restore planning",
+            "description": "Work out which backup jobs and volumes are needed to restore a path of a client as of a date.",
+            "arguments": [
+                argument("client", "Client the files were backed up from", true),
+                argument("path", "File or directory to restore", true),
+                argument("date", "Restore the state as of this date (YYYY-MM-DD); defaults to the latest backup", false)
+            ]
+        }),
+    ]
+}
+
+fn prompt(description: String, text: String) -> Value {
+    json!({
+        "description": description,
+        "messages": [
+            {
+                "role": "user",
+                "content": {
+                    "type": "text",
+                    "text": text
+                }
+            }
+        ]
+    })
+}
+
+/// Renders the named prompt with the `arguments` of a `prompts/get` request.
+pub fn get(name: &str, arguments: &Value) -> Result<Value> {
+    let arguments = Arguments::new(arguments)?;
+
+    let prompt = match name {
+        "daily_backup_review" => {
+            let client = arguments.optional_name("client")?;
+            let scope = match &client {
+                Some(client) => format!("client '{}'", client),
+                None => "all clients".to_string(),
+            };
+            let filter = match &client {
+                Some(client) => format!(", client: '{}'", client),
+                None => String::new(),
+            };
+            prompt(
+                format!("Daily backup review for {}", scope),
+                format!(
+                    "Review last night's Bareos backups for {scope}.\n\
+                     \n\
+                     1. Call `list_jobs` with {{jobtype: 'B', hours: 24{filter}}}. Only backup jobs \
+                     (jobtype B) count as backups; leave out verify, restore, copy and admin jobs.\n\
+                     2. Group the jobs by client. For each client give the number of jobs, the \
+                     levels (F/I/D) and the total bytes and files written.\n\
+                     3. For every job whose status is not T (terminated OK), call `get_job_log` \
+                     with its job_id and explain in one or two sentences why it failed and what \
+                     to do about it. Status W means it finished with warnings, f/E mean it failed, \
+                     A means it was canceled.\n\
+                     4. Point out clients that have no successful backup in the period and jobs \
+                     that are still running (status R) or waiting.\n\
+                     \n\
+                     Finish with a one-line overall verdict.",
+                    scope = scope,
+                    filter = filter
+                ),
+            )
+        }
+        "investigate_failed_job" => {
+            let job_id = arguments.job_id("jobid")?;
+            prompt(
+                format!("Investigate failed job {}", job_id),
+                format!(
+                    "Investigate why Bareos job {job_id} failed.\n\
+                     \n\
+                     1. Call `get_job_status` with job_id {job_id} for its name, client, level, \
+                     status, start/end time and error count.\n\
+                     2. Call `get_job_log` with job_id {job_id} and find the first error. Later \
+                     errors are often consequences of the first one.\n\
+                     3. Call `list_jobs` with the job name and {{days: 14}} to see whether this is \
+                     a one-off or keeps happening, and when it last succeeded.\n\
+                     4. If the log points at the job configuration, call `show_job` for it.\n\
+                     \n\
+                     Explain the root cause in plain language, quote the relevant log lines, and \
+                     suggest concrete next steps (e.g. fix the client connection, free up \
+                     storage, mount a volume).",
+                    job_id = job_id
+                ),
+            )
+        }
+        "volume_capacity_review" => {
+            let pool = arguments.optional_name("pool")?;
+            let scope = match &pool {
+                Some(pool) => format!("pool '{}'", pool),
+                None => "all pools".to_string(),
+            };
+            let filter = match &pool {
+                Some(pool) => format!("{{pool: '{}'}}", pool),
+                None => "no arguments".to_string(),
+            };
+            prompt(
+                format!("Volume capacity review for {}", scope),
+                format!(
+                    "Review Bareos volume capacity for {scope}.\n\
+                     \n\
+                     1. Call `list_pools` for the number of volumes and limits of each pool.\n\
+                     2. Call `list_volumes` with {filter}. Group the volumes by pool and status: \
+                     Append volumes can still be written, Full and Used volumes cannot, Purged \
+                     and Recycle volumes can be reused, Error and Disabled volumes need \
+                     attention.\n\
+                     3. For each pool give the bytes stored, how many volumes can still take \
+                     data, and how many are waiting for their retention to expire.\n\
+                     \n\
+                     Flag pools that are close to running out of writable volumes and volumes in \
+                     Error status.",
+                    scope = scope,
+                    filter = filter
+                ),
+            )
+        }
+        "restore_planning" => {
+            let client = arguments.name("client")?;
+            let path = arguments
+                .optional_path("path")?
+                .ok_or_else(|| InvalidParams {
+                    argument: "path".to_string(),
+                    reason: "is required".to_string(),
+                })?;
+            let date = arguments.optional_date("date")?;
+            let point = match &date {
+                Some(date) => format!("as of {}", date),
+                None => "from the latest backup".to_string(),
+            };
+            let date_filter = match &date {
+                Some(date) => format!(", date: '{}'", date),
+                None => String::new(),
+            };
+            prompt(
+                format!("Restore planning for {} on {}", path, client),
+                format!(
+                    "Plan a restore of `{path}` on client '{client}' {point}. Do not start a \
+                     restore; only work out what it needs.\n\
+                     \n\
+                     1. Call `plan_restore` with {{client: '{client}', paths: ['{path}']{date_filter}}}. \
+                     It picks the backup {point} and returns the Full, Differential and \
+                     Incremental jobs the restore needs, their volumes with status and storage, \
+                     whether the path is in the backup, an estimate of the size and warnings.\n\
+                     2. If the path was not found, call `browse_backup` with {{client: '{client}', \
+                     path: <the parent directory>{date_filter}}} and look for it under a \
+                     different name or location.\n\
+                     3. If the path is a file, call `list_file_versions` with {{client: '{client}', \
+                     path: '{path}'}} to show which other backups hold a version of it, in case an \
+                     older or newer one is wanted.\n\
+                     \n\
+                     Summarize the jobs, volumes and expected size of the restore, and mention \
+                     anything in the warnings that would prevent it, such as purged or recycled \
+                     volumes, a missing Full backup or backups of several FileSets.",
+                    path = path,
+                    client = client,
+                    point = point,
+                    date_filter = date_filter
+                ),
+            )
+        }
+        _ => {
+            return Err(InvalidParams {
+                argument: "name".to_string(),
+                reason: format!("unknown prompt '{}'", name),
+            }
+            .into())
+        }
+    };
+    Ok(prompt)
+}
@@ -0,0 +1,246 @@
+//! Masks secrets in Director output before it is handed to the model.
+//!
+//! Every value that is masked is counted for the running task, so the server can tell the
+//! caller how much of a tool result was hidden (see `counted`).
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde_json::Value;
+use std::cell::Cell;
+use std::future::Future;
+
+const REDACTED: &str = "[redacted]";
+
+// Credentials on command lines and in run scripts, e.g. `--password=secret` or `token: x`
+const CREDENTIAL_PATTERN: &str = r#"(?i)\b(?:password|passwd|pwd|secret|token|api[_-]?key)\s*[=:]\s*("[^"]*"|'[^']*'|[^\s"',;]+)"#;
+
+// Bareos stores hashed passwords as `[md5]<hex>`
+const MD5_PATTERN: &str = r"\[md5\][0-9A-Fa-f]+";
+
+tokio::task_local! {
+    static REDACTIONS: Cell<usize>;
+}
+
+/// Runs `future` and returns its output along with the number of values redacted while it
+/// ran on this task.
+pub async fn counted<F: Future>(future: F) -> (F::Output, usize) {
+    REDACTIONS
+        .scope(Cell::new(0), async {
+            let output = future.await;
+            (output, REDACTIONS.with(Cell::get))
+        })
+        .await
+}
+
+fn record(count: usize) {
+    // Outside of `counted` nobody is interested in the number
+    let _ = REDACTIONS.try_with(|redactions| redactions.set(redactions.get() + count));
+}
+
+/// Secret directives plus regular expressions for anything else that must not leave the
+/// server.
+pub struct Redactor {
+    patterns: Vec<Regex>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new(&[]).expect("built-in patterns are valid")
+    }
+}
+
+impl Redactor {
+    /// Adds `extra` patterns to the built-in ones. A pattern with a capture group only masks
+    /// the first group, so `(?i)apikey (\S+)` keeps the `apikey` label.
+    pub fn new(extra: &[String]) -> Result<Self> {
+        let mut patterns = vec![Regex::new(CREDENTIAL_PATTERN)?, Regex::new(MD5_PATTERN)?];
+        for pattern in extra {
+            patterns.push(
+                Regex::new(pattern)
+                    .with_context(|| format!("Invalid redaction pattern {:?}", pattern))?,
+            );
+        }
+        Ok(Self { patterns })
+    }
+
+    /// Reads extra patterns from `BAREOS_REDACT_PATTERNS`, a JSON array of strings.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("BAREOS_REDACT_PATTERNS") {
+            Ok(patterns) => {
+                let patterns: Vec<String> = serde_json::from_str(&patterns).context(
+                    "BAREOS_REDACT_PATTERNS must be a JSON array of regular expressions",
+                )?;
+                Self::new(&patterns)
+            }
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Masks secrets in command output and counts them.
+    pub fn redact(&self, text: &str) -> String {
+        let (text, mut count) = redact_directives(text);
+        let mut text = text;
+        for pattern in &self.patterns {
+            let (redacted, matches) = replace(pattern, &text);
+            text = redacted;
+            count += matches;
+        }
+        record(count);
+        text
+    }
+
+    /// Masks secrets in every string of a parsed `.api` reply.
+    pub fn redact_value(&self, value: &mut Value) {
+        self.redact_value_except(value, &[]);
+    }
+
+    /// Like `redact_value`, but leaves string fields named in `keep` alone, e.g. the file
+    /// and path names of catalog records, which the patterns would mangle.
+    pub fn redact_value_except(&self, value: &mut Value, keep: &[&str]) {
+        match value {
+            Value::String(text) => {
+                let redacted = self.redact(text);
+                *text = redacted;
+            }
+            Value::Array(items) => items
+                .iter_mut()
+                .for_each(|item| self.redact_value_except(item, keep)),
+            Value::Object(fields) => fields
+                .iter_mut()
+                .filter(|(name, field)| !(field.is_string() && keep.contains(&name.as_str())))
+                .for_each(|(_, field)| self.redact_value_except(field, keep)),
+            _ => {}
+        }
+    }
+}
+
+fn replace(pattern: &Regex, text: &str) -> (String, usize) {
+    let mut count = 0;
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for captures in pattern.captures_iter(text) {
+        let secret = captures.get(1).or_else(|| captures.get(0)).expect("match");
+        // Values masked as directives already, e.g. `Password = "[redacted]"`
+        if secret.as_str().contains(REDACTED) || secret.as_str().is_empty() {
+            continue;
+        }
+        result.push_str(&text[last..secret.start()]);
+        result.push_str(REDACTED);
+        last = secret.end();
+        count += 1;
+    }
+    result.push_str(&text[last..]);
+    (result, count)
+}
+
+// Directive names are case-insensitive and may be written with spaces ("Tls Psk"); anything
+// else before the `=` (`mysqldump --password=x`) is left to the patterns
+fn is_secret_directive(name: &str) -> bool {
+    if !name
+        .trim()
+        .chars()
+        .all(|c| c.is_ascii_alphabetic() || c == ' ')
+    {
+        return false;
+    }
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase();
+    name.contains("password")
+        || name.contains("secret")
+        || name.contains("psk")
+        || name.ends_with("key")
+}
+
+/// Replaces the values of password, PSK and key directives in `show` output, returning the
+/// text and how many values were replaced.
+fn redact_directives(text: &str) -> (String, usize) {
+    let mut count = 0;
+    let text = text
+        .split_inclusive('\n')
+        .map(|line| {
+            let content = line.trim_end_matches(['\r', '\n']);
+            match content.split_once('=') {
+                Some((name, value))
+                    if is_secret_directive(name)
+                        && !value.trim().is_empty()
+                        && !value.contains(REDACTED) =>
+                {
+                    count += 1;
+                    format!("{}= \"{}\"{}", name, REDACTED, &line[content.len()..])
+                }
+                _ => line.to_string(),
+            }
+        })
+        .collect();
+    (text, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_secret_directives() {
+        let show = "Client {\n  Name = \"web01-fd\"\n  Password = \"[md5]0123456789abcdef\"\n  TLS PSK = \"psk\"\n  TlsKey = \"/etc/bareos/web.key\"\n}\n";
+        assert_eq!(
+            redact_directives(show),
+            (
+                "Client {\n  Name = \"web01-fd\"\n  Password = \"[redacted]\"\n  TLS PSK = \"[redacted]\"\n  TlsKey = \"[redacted]\"\n}\n".to_string(),
+                3
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_other_directives() {
+        let show = "Pool {\n  Name = \"Full\"\n  Label Format = \"Full-\"\n}";
+        assert_eq!(redact_directives(show), (show.to_string(), 0));
+    }
+
+    #[tokio::test]
+    async fn counts_redactions() {
+        let redactor = Redactor::new(&["(?i)apikey (\\S+)".to_string()]).unwrap();
+        let (text, count) = counted(async {
+            redactor.redact(
+                "Password = \"x\"\nRun Script = \"dump --password=hunter2 --user=backup\"\napikey abc123\nhash [md5]0123abcd",
+            )
+        })
+        .await;
+        assert_eq!(
+            text,
+            "Password = \"[redacted]\"\nRun Script = \"dump --password=[redacted] --user=backup\"\napikey [redacted]\nhash [redacted]"
+        );
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn redacts_json_strings() {
+        let mut value = serde_json::json!({
+            "joblog": [{"logtext": "mysqldump --password=hunter2 db"}],
+            "jobid": 1
+        });
+        Redactor::default().redact_value(&mut value);
+        assert_eq!(
+            value["joblog"][0]["logtext"],
+            "mysqldump --password=[redacted] db"
+        );
+        assert_eq!(value["jobid"], 1);
+    }
+
+    #[test]
+    fn keeps_the_named_fields() {
+        let mut value = serde_json::json!([
+            {"filename": "/srv/app/token=abc.json", "md5": "[md5]0123abcd"},
+            {"name": "password: notes.txt", "nested": {"name": "pwd=x"}}
+        ]);
+        Redactor::default().redact_value_except(&mut value, &["filename", "name"]);
+        assert_eq!(value[0]["filename"], "/srv/app/token=abc.json");
+        assert_eq!(value[0]["md5"], "[redacted]");
+        assert_eq!(value[1]["name"], "password: notes.txt");
+        assert_eq!(value[1]["nested"]["name"], "pwd=x");
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::fmt;
+
+use crate::bareos::BareosClient;
+use crate::validate::resource_name;
+
+const SCHEME: &str = "bareos://";
+
+/// A `resources/read` URI that does not name an existing resource; reported as `-32002`.
+#[derive(Debug)]
+pub struct ResourceNotFound {
+    pub uri: String,
+}
+
+impl fmt::Display for ResourceNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Resource not found: {}", self.uri)
+    }
+}
+
+impl std::error::Error for ResourceNotFound {}
+
+/// A Bareos configuration or catalog object addressable by a `bareos://` URI.
+pub enum Resource {
+    JobConfig(String),
+    JobLog(u64),
+    Schedule(String),
+    Pool(String),
+}
+
+/// Resource templates as advertised by `resources/templates/list`.
+pub fn templates() -> Vec<Value> {
+    vec![
+        json!({
+            "uriTemplate": "bareos://job/{name}/config",
+            "name": "job-config",
+            "title": "Job configuration",
+            "description": "Configuration of a job resource as shown by `show job`",
+            "mimeType": "text/plain"
+        }),
+        json!({
+            "uriTemplate": "bareos://jobid/{id}/log",
+            "name": "job-log",
+            "title": "Job log",
+            "description": "Log messages of a job run, one per line",
+            "mimeType": "text/plain"
+        }),
+        json!({
+            "uriTemplate": "bareos://schedule/{name}",
+            "name": "schedule",
+            "title": "Schedule",
+            "description": "Configuration of a schedule resource as shown by `show schedule`",
+            "mimeType": "text/plain"
+        }),
+        json!({
+            "uriTemplate": "bareos://pool/{name}",
+            "name": "pool",
+            "title": "Pool",
+            "description": "Catalog record of a pool",
+            "mimeType": "application/json"
+        }),
+    ]
+}
+
+/// Concrete resources for `resources/list`. Job logs are only reachable through their
+/// template, as there is one per job run.
+pub async fn list(client: &BareosClient) -> Result<Vec<Resource>> {
+    let mut resources = Vec::new();
+    for name in client.job_names().await? {
+        resources.push(Resource::JobConfig(name));
+    }
+    for name in client.schedule_names().await? {
+        resources.push(Resource::Schedule(name));
+    }
+    for pool in client.list_pools().await? {
+        resources.push(Resource::Pool(pool.name));
+    }
+    Ok(resources)
+}
+
+// Names may contain spaces and colons, which are not allowed in a URI path segment
+fn encode(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn decode(segment: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut input = segment.bytes();
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let hex = [input.next()?, input.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+impl Resource {
+    pub fn parse(uri: &str) -> Result<Self> {
+        let not_found = || ResourceNotFound {
+            uri: uri.to_string(),
+        };
+        let path = uri.strip_prefix(SCHEME).ok_or_else(not_found)?;
+        let segments: Vec<&str> = path.split('/').collect();
+        let name = |segment: &str| -> Result<String> {
+            let name = decode(segment).ok_or_else(not_found)?;
+            Ok(resource_name("uri", &name)?)
+        };
+
+        Ok(match segments.as_slice() {
+            ["job", job, "config"] => Resource::JobConfig(name(job)?),
+            ["jobid", id, "log"] => {
+                Resource::JobLog(id.parse().ok().filter(|id| *id > 0).ok_or_else(not_found)?)
+            }
+            ["schedule", schedule] => Resource::Schedule(name(schedule)?),
+            ["pool", pool] => Resource::Pool(name(pool)?),
+            _ => return Err(not_found().into()),
+        })
+    }
+
+    pub fn uri(&self) -> String {
+        match self {
+            Resource::JobConfig(name) => format!("{}job/{}/config", SCHEME, encode(name)),
+            Resource::JobLog(job_id) => format!("{}jobid/{}/log", SCHEME, job_id),
+            Resource::Schedule(name) => format!("{}schedule/{}", SCHEME, encode(name)),
+            Resource::Pool(name) => format!("{}pool/{}", SCHEME, encode(name)),
+        }
+    }
+
+    fn mime_type(&self) -> &'static str {
+        match self {
+            Resource::Pool(_) => "application/json",
+            _ => "text/plain",
+        }
+    }
+
+    /// The entry for this resource in `resources/list`.
+    pub fn describe(&self) -> Value {
+        let (name, title) = match self {
+            Resource::JobConfig(name) => (name.clone(), format!("Job {}", name)),
+            Resource::JobLog(job_id) => (job_id.to_string(), format!("Log of job {}", job_id)),
+            Resource::Schedule(name) => (name.clone(), format!("Schedule {}", name)),
+            Resource::Pool(name) => (name.clone(), format!("Pool {}", name)),
+        };
+        json!({
+            "uri": self.uri(),
+            "name": name,
+            "title": title,
+            "mimeType": self.mime_type()
+        })
+    }
+
+    /// The tool call that reads the same data, so tool permissions apply to resources too.
+    pub fn equivalent_tool(&self) -> (&'static str, Value) {
+        match self {
+            Resource::JobConfig(name) => ("show_job", json!({ "job_name": name })),
+            Resource::JobLog(job_id) => ("get_job_log", json!({ "job_id": job_id })),
+            Resource::Schedule(name) => ("show_schedule", json!({ "schedule_name": name })),
+            Resource::Pool(name) => ("list_pools", json!({ "pool": name })),
+        }
+    }
+
+    /// Reads the resource as a `resources/read` content item.
+    pub async fn read(&self, client: &BareosClient) -> Result<Value> {
+        let not_found = || ResourceNotFound { uri: self.uri() };
+        let text = match self {
+            Resource::JobConfig(name) => client.show_job(name).await?,
+            Resource::JobLog(job_id) => {
+                let entries = client.get_job_log(*job_id).await?;
+                if entries.is_empty() {
+                    return Err(not_found().into());
+                }
+                entries
+                    .iter()
+                    .map(|entry| match &entry.time {
+                        Some(time) => format!("{} {}", time, entry.text.trim_end()),
+                        None => entry.text.trim_end().to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Resource::Schedule(name) => client.show_schedule(name).await?,
+            Resource::Pool(name) => {
+                let pool = client
+                    .list_pools()
+                    .await?
+                    .into_iter()
+                    .find(|pool| pool.name == *name)
+                    .ok_or_else(not_found)?;
+                serde_json::to_string_pretty(&pool)?
+            }
+        };
+
+        Ok(json!({
+            "uri": self.uri(),
+            "mimeType": self.mime_type(),
+            "text": text
+        }))
+    }
+}
@@ -0,0 +1,769 @@
+use anyhow::Result;
+use futures_util::future::join_all;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+use crate::auth::{self, Forbidden, Identity};
+use crate::bareos::{BareosClient, Timeout};
+use crate::completion::Completions;
+use crate::confirm::{Confirmations, Holder};
+use crate::prompts;
+use crate::redact;
+use crate::resources::{self, Resource, ResourceNotFound};
+use crate::tools;
+use crate::validate::InvalidParams;
+
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(600);
+
+// Methods that talk to the Director, and so run under the request timeout and can be
+// cancelled
+const CANCELLABLE_METHODS: &[&str] = &[
+    "tools/call",
+    "resources/list",
+    "resources/read",
+    "completion/complete",
+];
+
+// Running requests by session and JSON-RPC id, with the sequence number of their
+// registration; sending on the channel cancels the request
+type InFlight = Arc<Mutex<HashMap<String, (u64, oneshot::Sender<()>)>>>;
+
+/// A request registered for cancellation; dropping it forgets the registration, however the
+/// request ended.
+struct Registration {
+    in_flight: InFlight,
+    key: String,
+    sequence: u64,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        // A cancelled request's id may already be registered again by a newer request
+        if in_flight
+            .get(&self.key)
+            .is_some_and(|(sequence, _)| *sequence == self.sequence)
+        {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+/// A JSON-RPC message whose cancellation is already registered, to be handled later with
+/// `Server::handle_registered_request`.
+pub struct RegisteredRequest {
+    session: String,
+    request: Value,
+    // The registration of a cancellable request, or the error for an id already in use
+    registration: Result<Option<(Registration, oneshot::Receiver<()>)>, Value>,
+}
+
+/// JSON-RPC dispatcher for the MCP methods, independent of the transport.
+pub struct Server {
+    client: BareosClient,
+    confirmations: Confirmations,
+    completions: Completions,
+    request_timeout: Duration,
+    in_flight: InFlight,
+    registrations: AtomicU64,
+}
+
+impl Server {
+    pub fn new(client: BareosClient) -> Self {
+        Self {
+            client,
+            confirmations: Confirmations::default(),
+            completions: Completions::default(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            registrations: AtomicU64::new(0),
+        }
+    }
+
+    /// Limits how long a `tools/call`, `resources/list`, `resources/read` or
+    /// `completion/complete` may take in total, across all commands it sends.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sets how long confirmation tokens for write tools stay valid.
+    pub fn with_confirmation_ttl(mut self, ttl: Duration) -> Self {
+        self.confirmations = Confirmations::new(ttl);
+        self
+    }
+
+    pub fn client(&self) -> &BareosClient {
+        &self.client
+    }
+
+    /// Handles one JSON-RPC message from the local, unrestricted caller and returns the
+    /// response, if any.
+    pub async fn handle_request(&self, request: Value) -> Option<Value> {
+        self.handle_queued_request(request, async {}).await
+    }
+
+    /// Like `handle_request`, but waits for `permit` (e.g. a transport's concurrency limit)
+    /// before running the request, and holds it until the response is ready.
+    pub async fn handle_queued_request<P>(
+        &self,
+        request: Value,
+        permit: impl Future<Output = P>,
+    ) -> Option<Value> {
+        let registered = self.register_request("", request);
+        self.handle_registered_request(registered, permit).await
+    }
+
+    /// Like `handle_queued_request`, for a request registered with `register_request`.
+    pub async fn handle_registered_request<P>(
+        &self,
+        registered: RegisteredRequest,
+        permit: impl Future<Output = P>,
+    ) -> Option<Value> {
+        let identity = Identity::unrestricted("local");
+        self.handle_registered(&identity, registered, permit).await
+    }
+
+    /// Like `handle_request`, for transports that multiplex several authenticated clients.
+    ///
+    /// `session` scopes request ids, so a client can only cancel its own requests, and
+    /// `identity` limits the tools and catalog records it can reach.
+    /// Cancelled requests get no response, as the MCP spec requires.
+    pub async fn handle_session_request(
+        &self,
+        session: &str,
+        identity: &Identity,
+        request: Value,
+    ) -> Option<Value> {
+        self.handle_queued_session_request(session, identity, request, async {})
+            .await
+    }
+
+    /// Like `handle_session_request`, but waits for `permit` first, see
+    /// `handle_queued_request`.
+    pub async fn handle_queued_session_request<P>(
+        &self,
+        session: &str,
+        identity: &Identity,
+        request: Value,
+        permit: impl Future<Output = P>,
+    ) -> Option<Value> {
+        let registered = self.register_request(session, request);
+        self.handle_registered(identity, registered, permit).await
+    }
+
+    /// Registers `request` for cancellation without running it, so a transport can hand it
+    /// to another task and still have a cancellation read right after it find it.
+    pub fn register_request(&self, session: &str, request: Value) -> RegisteredRequest {
+        let method = request["method"].as_str().unwrap_or("");
+        let registration = match request.get("id") {
+            Some(id) if CANCELLABLE_METHODS.contains(&method) => match self.register(session, id) {
+                Some(registration) => Ok(Some(registration)),
+                None => {
+                    warn!("Refusing request {}: its id is already in use", id);
+                    Err(json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {
+                            "code": -32600,
+                            "message": format!("Request id {} is already in use", id)
+                        }
+                    }))
+                }
+            },
+            _ => Ok(None),
+        };
+        RegisteredRequest {
+            session: session.to_string(),
+            request,
+            registration,
+        }
+    }
+
+    async fn handle_registered<P>(
+        &self,
+        identity: &Identity,
+        registered: RegisteredRequest,
+        permit: impl Future<Output = P>,
+    ) -> Option<Value> {
+        let RegisteredRequest {
+            session,
+            request,
+            registration,
+        } = registered;
+        let session = session.as_str();
+        if request["method"] == "notifications/cancelled" {
+            self.cancel(session, &request["params"]["requestId"]);
+            return None;
+        }
+
+        // JSON-RPC notifications omit "id" and MUST NOT receive a response.
+        request.get("id")?;
+
+        let method = request["method"].as_str().unwrap_or("");
+        let id = request["id"].clone();
+
+        // Registered before waiting for the permit, so a cancellation that arrives while the
+        // request is queued is not lost; the registration is dropped with this future
+        let (_registration, mut cancelled) = match registration {
+            Ok(Some((registration, cancelled))) => (Some(registration), Some(cancelled)),
+            Ok(None) => (None, None),
+            Err(refused) => return Some(refused),
+        };
+        let _permit = match &mut cancelled {
+            Some(receiver) => tokio::select! {
+                permit = permit => permit,
+                _ = receiver => return None,
+            },
+            None => permit.await,
+        };
+
+        Some(match method {
+            "initialize" => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {
+                    "protocolVersion": "2025-06-18",
+                    "capabilities": {
+                        "tools": {},
+                        "resources": {},
+                        "prompts": {},
+                        "completions": {}
+                    },
+                    "serverInfo": {
+                        "name": "bareos-mcp-server",
+                        "version": "0.1.0"
+                    }
+                }
+            }),
+            "tools/list" => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {
+                    "tools": tools::definitions(self.client.write_mode())
+                        .into_iter()
+                        .filter(|tool| identity.allows_tool(tool["name"].as_str().unwrap_or("")))
+                        .collect::<Vec<_>>()
+                }
+            }),
+            "resources/list" => {
+                let listing = self.list_resources(identity);
+                let cancelled = cancelled.expect("resource listings are registered above");
+                let result = self.run("Resource listing", listing, cancelled).await?;
+                respond(&id, "Resource listing failed", result)
+            }
+            "resources/templates/list" => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {
+                    "resourceTemplates": resources::templates()
+                }
+            }),
+            "resources/read" => {
+                let read = self.read_resource(identity, &request["params"]);
+                let cancelled = cancelled.expect("resource reads are registered above");
+                let result = self.run("Resource read", read, cancelled).await?;
+                respond(&id, "Resource read failed", result)
+            }
+            "prompts/list" => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {
+                    "prompts": prompts::definitions()
+                }
+            }),
+            "prompts/get" => respond(
+                &id,
+                "Prompt rendering failed",
+                prompts::get(
+                    request["params"]["name"].as_str().unwrap_or(""),
+                    &request["params"]["arguments"],
+                ),
+            ),
+            "completion/complete" => {
+                let completion =
+                    self.completions
+                        .complete(&self.client, identity, &request["params"]);
+                let cancelled = cancelled.expect("completions are registered above");
+                let result = self.run("Completion", completion, cancelled).await?;
+                respond(&id, "Completion failed", result)
+            }
+            "tools/call" => {
+                let cancelled = cancelled.expect("tool calls are registered above");
+                self.call_tool(session, identity, &id, &request["params"], cancelled)
+                    .await?
+            }
+            _ => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {
+                    "code": -32601,
+                    "message": format!("Method not found: {}", method)
+                }
+            }),
+        })
+    }
+
+    // Registers a request for cancellation, unless a running request of the session already
+    // has its id: that one would no longer be cancellable
+    fn register(&self, session: &str, id: &Value) -> Option<(Registration, oneshot::Receiver<()>)> {
+        let key = in_flight_key(session, id);
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight.contains_key(&key) {
+            return None;
+        }
+        let sequence = self.registrations.fetch_add(1, Ordering::Relaxed);
+        let (cancel, receiver) = oneshot::channel();
+        in_flight.insert(key.clone(), (sequence, cancel));
+        let registration = Registration {
+            in_flight: Arc::clone(&self.in_flight),
+            key,
+            sequence,
+        };
+        Some((registration, receiver))
+    }
+
+    // Runs `request` under the request timeout until it finishes or is cancelled; `None` when
+    // it was cancelled. Dropping the future on timeout or cancellation also drops any session
+    // it holds, which kills the bconsole process or closes the Director connection
+    async fn run<T>(
+        &self,
+        operation: &str,
+        request: impl Future<Output = Result<T>>,
+        cancelled: oneshot::Receiver<()>,
+    ) -> Option<Result<T>> {
+        let result = tokio::select! {
+            result = tokio::time::timeout(self.request_timeout, request) => Some(result),
+            _ = cancelled => None,
+        };
+        Some(result?.unwrap_or_else(|_| {
+            Err(Timeout {
+                operation: operation.to_string(),
+                after: self.request_timeout,
+            }
+            .into())
+        }))
+    }
+
+    /// Cancels the in-flight request with the given JSON-RPC id, if it is still running.
+    pub fn cancel(&self, session: &str, request_id: &Value) {
+        if let Some((_, cancel)) = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .remove(&in_flight_key(session, request_id))
+        {
+            info!("Cancelling request {}", request_id);
+            let _ = cancel.send(());
+        }
+    }
+
+    async fn call_tool(
+        &self,
+        session: &str,
+        identity: &Identity,
+        id: &Value,
+        params: &Value,
+        cancelled: oneshot::Receiver<()>,
+    ) -> Option<Value> {
+        let tool_name = params["name"].as_str().unwrap_or("");
+        let arguments = &params["arguments"];
+
+        let holder = Holder::new(session, &identity.name);
+        let call = async {
+            let (output, redactions) = redact::counted(async {
+                auth::authorize(&self.client, identity, tool_name, arguments).await?;
+                tools::call(
+                    &self.client,
+                    &self.confirmations,
+                    &holder,
+                    tool_name,
+                    arguments,
+                )
+                .await
+                .map(|output| auth::filter_output(identity, output))
+            })
+            .await;
+            output.map(|output| with_redactions(output.into_result(), redactions))
+        };
+        let operation = format!("Tool '{}'", tool_name);
+        let result = self.run(&operation, call, cancelled).await?;
+
+        Some(respond(id, "Tool execution failed", result))
+    }
+
+    async fn list_resources(&self, identity: &Identity) -> Result<Value> {
+        let resources = resources::list(&self.client).await?;
+        // Checked side by side, as a scoped identity looks up the defaults of every job
+        let allowed = join_all(resources.iter().map(|resource| async move {
+            let (tool, arguments) = resource.equivalent_tool();
+            auth::authorize(&self.client, identity, tool, &arguments)
+                .await
+                .is_ok()
+        }))
+        .await;
+        let listed: Vec<Value> = resources
+            .iter()
+            .zip(allowed)
+            .filter(|(_, allowed)| *allowed)
+            .map(|(resource, _)| resource.describe())
+            .collect();
+        Ok(json!({ "resources": listed }))
+    }
+
+    async fn read_resource(&self, identity: &Identity, params: &Value) -> Result<Value> {
+        let uri = params["uri"].as_str().ok_or_else(|| InvalidParams {
+            argument: "uri".to_string(),
+            reason: "is required".to_string(),
+        })?;
+        let resource = Resource::parse(uri)?;
+
+        let (tool, arguments) = resource.equivalent_tool();
+        auth::authorize(&self.client, identity, tool, &arguments).await?;
+
+        let (contents, redactions) = redact::counted(resource.read(&self.client)).await;
+        Ok(with_redactions(
+            json!({ "contents": [contents?] }),
+            redactions,
+        ))
+    }
+}
+
+// Tells the client that part of the result was masked, so it does not take a
+// `[redacted]` value for the real one
+fn with_redactions(mut result: Value, redactions: usize) -> Value {
+    if redactions > 0 {
+        result["_meta"] = json!({ "bareos/redactions": redactions });
+    }
+    result
+}
+
+fn respond(id: &Value, context: &str, result: Result<Value>) -> Value {
+    match result {
+        Ok(result) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result
+        }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": error_object(context, &e)
+        }),
+    }
+}
+
+// Errors a client can act on get their own code; everything else is a generic failure
+fn error_object(context: &str, e: &anyhow::Error) -> Value {
+    if let Some(invalid) = e.downcast_ref::<InvalidParams>() {
+        json!({
+            "code": -32602,
+            "message": invalid.to_string(),
+            "data": {
+                "argument": invalid.argument
+            }
+        })
+    } else if let Some(not_found) = e.downcast_ref::<ResourceNotFound>() {
+        json!({
+            "code": -32002,
+            "message": not_found.to_string(),
+            "data": {
+                "uri": not_found.uri
+            }
+        })
+    } else if let Some(forbidden) = e.downcast_ref::<Forbidden>() {
+        json!({
+            "code": -32003,
+            "message": forbidden.to_string(),
+            "data": {
+                "identity": forbidden.identity
+            }
+        })
+    } else if let Some(timeout) = e.downcast_ref::<Timeout>() {
+        json!({
+            "code": -32001,
+            "message": timeout.to_string(),
+            "data": {
+                "timeout_secs": timeout.after.as_secs()
+            }
+        })
+    } else {
+        json!({
+            "code": -32000,
+            "message": format!("{}: {}", context, e)
+        })
+    }
+}
+
+fn in_flight_key(session: &str, request_id: &Value) -> String {
+    format!("{}/{}", session, request_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBconsole;
+    use std::sync::Arc;
+    use tokio::sync::Semaphore;
+
+    fn call(id: u64, tool: &str) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": tool, "arguments": {} }
+        })
+    }
+
+    fn cancellation(id: u64) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/cancelled",
+            "params": { "requestId": id }
+        })
+    }
+
+    async fn wait_until_registered(server: &Server, id: u64) {
+        let key = in_flight_key("", &json!(id));
+        while !server.in_flight.lock().unwrap().contains_key(&key) {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn cancels_calls_still_waiting_for_a_permit() {
+        let fake = FakeBconsole::new("");
+        let server = Arc::new(Server::new(fake.client()));
+        let permits = Arc::new(Semaphore::new(1));
+        let held = Arc::clone(&permits).acquire_owned().await.unwrap();
+
+        let queued = tokio::spawn({
+            let server = Arc::clone(&server);
+            let permits = Arc::clone(&permits);
+            async move {
+                server
+                    .handle_queued_request(call(7, "list_clients"), permits.acquire_owned())
+                    .await
+            }
+        });
+        wait_until_registered(&server, 7).await;
+        assert!(server.handle_request(cancellation(7)).await.is_none());
+
+        assert!(queued.await.unwrap().is_none());
+        assert!(server.in_flight.lock().unwrap().is_empty());
+        drop(held);
+        assert!(fake.commands().is_empty());
+    }
+
+    // The first `list clients` records the session's pid and blocks; later ones answer
+    const BLOCKING: &str = r#"
+  "list clients")
+    if [ -e "$dir/blocked.pid" ]; then
+      echo '{"result":{"clients":[{"clientid":1,"name":"web01-fd"}]}}'
+    else
+      echo $$ > "$dir/blocked.pid.tmp" && mv "$dir/blocked.pid.tmp" "$dir/blocked.pid"
+      exec sleep 60
+    fi ;;"#;
+
+    async fn blocked_pid(fake: &FakeBconsole) -> u32 {
+        loop {
+            if let Ok(pid) = std::fs::read_to_string(fake.dir().join("blocked.pid")) {
+                return pid.trim().parse().unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    // Killed and reaped, or at least killed and waiting to be reaped
+    async fn wait_until_dead(pid: u32) {
+        let stat = format!("/proc/{}/stat", pid);
+        for _ in 0..500 {
+            match std::fs::read_to_string(&stat) {
+                Err(_) => return,
+                Ok(stat)
+                    if stat
+                        .rsplit(')')
+                        .next()
+                        .unwrap()
+                        .trim_start()
+                        .starts_with('Z') =>
+                {
+                    return
+                }
+                Ok(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        }
+        panic!("bconsole session {} is still running", pid);
+    }
+
+    // The pool holds a single session, so this only answers if the blocked one was dropped
+    async fn assert_session_replaced(server: &Server) {
+        let response = server
+            .handle_request(call(9, "list_clients"))
+            .await
+            .unwrap();
+        assert!(response.get("error").is_none(), "{}", response);
+        assert_eq!(
+            response["result"]["structuredContent"]["clients"][0]["name"],
+            "web01-fd"
+        );
+    }
+
+    #[tokio::test]
+    async fn timeouts_drop_the_blocked_session() {
+        let fake = FakeBconsole::new(BLOCKING);
+        let server = Server::new(fake.client()).with_request_timeout(Duration::from_millis(300));
+
+        let response = server
+            .handle_request(call(7, "list_clients"))
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], -32001, "{}", response);
+
+        wait_until_dead(blocked_pid(&fake).await).await;
+        assert!(server.in_flight.lock().unwrap().is_empty());
+        assert_session_replaced(&server).await;
+    }
+
+    #[tokio::test]
+    async fn cancellation_drops_the_blocked_session() {
+        let fake = FakeBconsole::new(BLOCKING);
+        let server = Arc::new(Server::new(fake.client()));
+
+        let running = tokio::spawn({
+            let server = Arc::clone(&server);
+            async move { server.handle_request(call(7, "list_clients")).await }
+        });
+        let pid = blocked_pid(&fake).await;
+        assert!(server.handle_request(cancellation(7)).await.is_none());
+
+        assert!(running.await.unwrap().is_none());
+        wait_until_dead(pid).await;
+        assert!(server.in_flight.lock().unwrap().is_empty());
+        assert_session_replaced(&server).await;
+    }
+
+    // `.jobs` never answers, so resource listings and job name completions hang
+    const BLOCKING_JOBS: &str = r#"
+  ".jobs") touch "$dir/started"; exec sleep 60 ;;"#;
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn directory_requests() -> Vec<Value> {
+        vec![
+            request(7, "resources/list", json!({})),
+            request(
+                7,
+                "completion/complete",
+                json!({
+                    "ref": { "type": "ref/prompt", "name": "failed-jobs" },
+                    "argument": { "name": "job", "value": "back" }
+                }),
+            ),
+        ]
+    }
+
+    #[tokio::test]
+    async fn times_out_resource_listings_and_completions() {
+        let fake = FakeBconsole::new(BLOCKING_JOBS);
+        let server = Server::new(fake.client()).with_request_timeout(Duration::from_millis(300));
+        for request in directory_requests() {
+            let response = server.handle_request(request).await.unwrap();
+            assert_eq!(response["error"]["code"], -32001, "{}", response);
+            assert!(server.in_flight.lock().unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn cancels_resource_listings_and_completions() {
+        for request in directory_requests() {
+            let fake = FakeBconsole::new(BLOCKING_JOBS);
+            let server = Arc::new(Server::new(fake.client()));
+            let running = tokio::spawn({
+                let server = Arc::clone(&server);
+                async move { server.handle_request(request).await }
+            });
+            while !fake.dir().join("started").exists() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert!(server.handle_request(cancellation(7)).await.is_none());
+
+            assert!(running.await.unwrap().is_none());
+            assert!(server.in_flight.lock().unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn forgets_requests_whose_future_is_dropped() {
+        let fake = FakeBconsole::new(BLOCKING);
+        let server = Arc::new(Server::new(fake.client()));
+
+        // Queued behind a permit that never comes
+        let queued =
+            server.handle_queued_request(call(7, "list_clients"), std::future::pending::<()>());
+        assert!(tokio::time::timeout(Duration::from_millis(50), queued)
+            .await
+            .is_err());
+        assert!(server.in_flight.lock().unwrap().is_empty());
+
+        // Running, as when an SSE client disconnects
+        let running = tokio::spawn({
+            let server = Arc::clone(&server);
+            async move { server.handle_request(call(8, "list_clients")).await }
+        });
+        let pid = blocked_pid(&fake).await;
+        running.abort();
+        assert!(running.await.unwrap_err().is_cancelled());
+        assert!(server.in_flight.lock().unwrap().is_empty());
+        wait_until_dead(pid).await;
+    }
+
+    #[tokio::test]
+    async fn cancels_registered_requests_before_they_start() {
+        let fake = FakeBconsole::new("");
+        let server = Server::new(fake.client());
+
+        let registered = server.register_request("", call(7, "list_clients"));
+        assert!(server.handle_request(cancellation(7)).await.is_none());
+        assert!(server
+            .handle_registered_request(registered, async {})
+            .await
+            .is_none());
+        assert!(fake.commands().is_empty());
+        assert!(server.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuses_ids_of_requests_still_running() {
+        let fake = FakeBconsole::new("");
+        let server = Arc::new(Server::new(fake.client()));
+        let queued = tokio::spawn({
+            let server = Arc::clone(&server);
+            async move {
+                server
+                    .handle_queued_request(call(7, "list_clients"), std::future::pending::<()>())
+                    .await
+            }
+        });
+        wait_until_registered(&server, 7).await;
+
+        let duplicate = server
+            .handle_queued_request(call(7, "list_clients"), std::future::pending::<()>())
+            .await
+            .unwrap();
+        assert_eq!(duplicate["error"]["code"], -32600, "{}", duplicate);
+
+        // The first request is still the one a cancellation reaches
+        assert!(server.handle_request(cancellation(7)).await.is_none());
+        assert!(queued.await.unwrap().is_none());
+        assert!(server.in_flight.lock().unwrap().is_empty());
+    }
+}
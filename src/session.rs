@@ -0,0 +1,427 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::future::Future;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{Mutex, Semaphore};
+use tracing::{debug, warn};
+
+static MARKER_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+// bconsole handles `@echo` locally, so the marker line is printed only after the
+// director has finished answering the preceding command.
+fn next_marker() -> String {
+    format!(
+        "__bareos_mcp_{}_{}__",
+        std::process::id(),
+        MARKER_SEQUENCE.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// A command or request that did not finish within its time limit.
+#[derive(Debug)]
+pub struct Timeout {
+    pub operation: String,
+    pub after: Duration,
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} timed out after {} seconds",
+            self.operation,
+            self.after.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+/// A session that failed before a command was written to it, so the command did not run
+/// and can be sent again on a new session.
+#[derive(Debug)]
+pub struct NotSent;
+
+impl fmt::Display for NotSent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Session failed before the command was sent")
+    }
+}
+
+impl std::error::Error for NotSent {}
+
+/// A long-lived connection that commands can be sent over one at a time. Failures to write
+/// the command carry `NotSent`.
+pub trait Session: Send + Sized {
+    fn execute(&mut self, command: &str) -> impl Future<Output = Result<String>> + Send;
+}
+
+/// Opens new sessions for a `SessionPool`.
+pub trait Connect: Send + Sync {
+    type Session: Session;
+
+    fn connect(&self) -> impl Future<Output = Result<Self::Session>> + Send;
+}
+
+pub struct Bconsole {
+    pub path: String,
+}
+
+impl Connect for Bconsole {
+    type Session = BconsoleSession;
+
+    async fn connect(&self) -> Result<BconsoleSession> {
+        BconsoleSession::spawn(&self.path).await
+    }
+}
+
+pub struct BconsoleSession {
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl BconsoleSession {
+    async fn spawn(bconsole_path: &str) -> Result<Self> {
+        let mut child = Command::new(bconsole_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to spawn bconsole")?;
+
+        let stdin = child.stdin.take().context("Failed to open stdin")?;
+        let stdout = child.stdout.take().context("Failed to open stdout")?;
+
+        // bconsole writes connection info to stderr; drain it so the pipe never fills up
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    debug!("bconsole: {}", line);
+                }
+            });
+        }
+
+        let mut session = Self {
+            _child: child,
+            stdin,
+            stdout: BufReader::new(stdout),
+        };
+
+        // Consume the connection banner so it does not end up in the first command's output
+        let marker = next_marker();
+        session.send(&format!("@echo {}\n", marker)).await?;
+        session
+            .read_until_marker(&marker)
+            .await
+            .context("bconsole did not become ready")?;
+
+        Ok(session)
+    }
+
+    async fn send(&mut self, input: &str) -> Result<()> {
+        self.stdin
+            .write_all(input.as_bytes())
+            .await
+            .context("Failed to write to bconsole")?;
+        self.stdin
+            .flush()
+            .await
+            .context("Failed to write to bconsole")
+    }
+
+    async fn read_until_marker(&mut self, marker: &str) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+        let mut buf = Vec::new();
+
+        loop {
+            buf.clear();
+            let read = self
+                .stdout
+                .read_until(b'\n', &mut buf)
+                .await
+                .context("Failed to read bconsole output")?;
+            if read == 0 {
+                anyhow::bail!(
+                    "bconsole session terminated unexpectedly: {}",
+                    lines.join("\n")
+                );
+            }
+
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line == marker {
+                return Ok(lines);
+            }
+            lines.push(line.to_string());
+        }
+    }
+}
+
+impl Session for BconsoleSession {
+    async fn execute(&mut self, command: &str) -> Result<String> {
+        let marker = next_marker();
+        let echo = format!("@echo {}", marker);
+
+        self.send(&format!("{}\n{}\n", command, echo))
+            .await
+            .context(NotSent)?;
+        let mut lines = self.read_until_marker(&marker).await?;
+
+        // Without a terminal bconsole echoes its input; drop those lines
+        if lines.last() == Some(&echo) {
+            lines.pop();
+        }
+        if lines.first().map(String::as_str) == Some(command) {
+            lines.remove(0);
+        }
+
+        let mut output = lines.join("\n");
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        Ok(output)
+    }
+}
+
+/// Output format a command should be answered in.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    Text,
+    Json,
+}
+
+impl OutputMode {
+    fn api_command(self) -> &'static str {
+        match self {
+            OutputMode::Text => ".api 0",
+            OutputMode::Json => ".api 2",
+        }
+    }
+}
+
+struct PooledSession<S> {
+    session: S,
+    mode: OutputMode,
+}
+
+impl<S: Session> PooledSession<S> {
+    async fn execute(&mut self, command: &str, mode: OutputMode) -> Result<String> {
+        // Switching modes is harmless to repeat, so its failure counts as not sent
+        if self.mode != mode {
+            self.session
+                .execute(mode.api_command())
+                .await
+                .context(NotSent)?;
+            self.mode = mode;
+        }
+        self.session.execute(command).await
+    }
+}
+
+/// Keeps a small number of sessions open and multiplexes commands over them.
+pub struct SessionPool<C: Connect> {
+    connector: C,
+    idle: Mutex<Vec<PooledSession<C::Session>>>,
+    permits: Semaphore,
+    timeout: Duration,
+}
+
+impl<C: Connect> SessionPool<C> {
+    pub fn new(connector: C, size: usize, timeout: Duration) -> Self {
+        Self {
+            connector,
+            idle: Mutex::new(Vec::new()),
+            permits: Semaphore::new(size.max(1)),
+            timeout,
+        }
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    async fn connect(&self) -> Result<PooledSession<C::Session>> {
+        Ok(PooledSession {
+            session: self.connector.connect().await?,
+            mode: OutputMode::Text,
+        })
+    }
+
+    /// Runs `command` on an idle session, giving up after the pool's timeout.
+    ///
+    /// A session is only returned to the pool once its command has completed, so one that
+    /// timed out or whose caller went away is dropped, which kills bconsole or closes the
+    /// Director connection. When the session turns out to be dead before the command was
+    /// written, it is sent once more on a new session.
+    pub async fn execute(&self, command: &str, mode: OutputMode) -> Result<String> {
+        self.execute_with(command, mode, true).await
+    }
+
+    /// Like `execute`, but never sends `command` a second time, for commands that change
+    /// Director state.
+    pub async fn execute_once(&self, command: &str, mode: OutputMode) -> Result<String> {
+        self.execute_with(command, mode, false).await
+    }
+
+    async fn execute_with(&self, command: &str, mode: OutputMode, retry: bool) -> Result<String> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .context("Session pool closed")?;
+
+        match tokio::time::timeout(self.timeout, self.execute_on_session(command, mode, retry))
+            .await
+        {
+            Ok(result) => result,
+            Err(_) => {
+                warn!("Command {:?} timed out, dropping its session", command);
+                Err(Timeout {
+                    operation: format!("Command {:?}", command),
+                    after: self.timeout,
+                }
+                .into())
+            }
+        }
+    }
+
+    async fn execute_on_session(
+        &self,
+        command: &str,
+        mode: OutputMode,
+        retry: bool,
+    ) -> Result<String> {
+        let idle = self.idle.lock().await.pop();
+        let mut session = match idle {
+            Some(session) => session,
+            None => self.connect().await?,
+        };
+
+        let output = match session.execute(command, mode).await {
+            Ok(output) => output,
+            // The session may have died while idle (e.g. a dropped SSH connection); once the
+            // command was written it may have run, so it is never sent twice
+            Err(e) if retry && e.downcast_ref::<NotSent>().is_some() => {
+                warn!("Session failed, restarting: {:#}", e);
+                session = self.connect().await?;
+                session.execute(command, mode).await?
+            }
+            Err(e) => return Err(e),
+        };
+
+        self.idle.lock().await.push(session);
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+
+    #[derive(Clone, Copy)]
+    enum Outcome {
+        Answer,
+        FailBeforeWrite,
+        FailAfterWrite,
+    }
+
+    // Hands out sessions that behave as listed, one per connect, and records the commands
+    // that reached a session
+    #[derive(Default)]
+    struct Stub {
+        outcomes: std::sync::Mutex<VecDeque<Outcome>>,
+        sent: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    struct StubSession {
+        outcome: Outcome,
+        sent: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl Connect for Stub {
+        type Session = StubSession;
+
+        async fn connect(&self) -> Result<StubSession> {
+            let outcome = self
+                .outcomes
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Outcome::Answer);
+            Ok(StubSession {
+                outcome,
+                sent: Arc::clone(&self.sent),
+            })
+        }
+    }
+
+    impl Session for StubSession {
+        async fn execute(&mut self, command: &str) -> Result<String> {
+            match self.outcome {
+                Outcome::FailBeforeWrite => Err(anyhow::anyhow!("Broken pipe").context(NotSent)),
+                Outcome::FailAfterWrite => {
+                    self.sent.lock().unwrap().push(command.to_string());
+                    anyhow::bail!("Command '{}' asked for interactive input", command)
+                }
+                Outcome::Answer => {
+                    self.sent.lock().unwrap().push(command.to_string());
+                    Ok(format!("ran {}", command))
+                }
+            }
+        }
+    }
+
+    fn pool(outcomes: &[Outcome]) -> SessionPool<Stub> {
+        let stub = Stub {
+            outcomes: std::sync::Mutex::new(outcomes.iter().copied().collect()),
+            ..Default::default()
+        };
+        SessionPool::new(stub, 1, Duration::from_secs(5))
+    }
+
+    fn sent(pool: &SessionPool<Stub>) -> Vec<String> {
+        pool.connector.sent.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn resends_commands_that_were_not_written() {
+        let pool = pool(&[Outcome::FailBeforeWrite]);
+        let output = pool.execute("list jobs", OutputMode::Text).await.unwrap();
+        assert_eq!(output, "ran list jobs");
+        assert_eq!(sent(&pool), vec!["list jobs"]);
+    }
+
+    #[tokio::test]
+    async fn never_resends_commands_that_were_written() {
+        let pool = pool(&[Outcome::FailAfterWrite]);
+        let error = pool.execute("status dir", OutputMode::Text).await;
+        assert!(error.unwrap_err().to_string().contains("interactive input"));
+        assert_eq!(sent(&pool), vec!["status dir"]);
+    }
+
+    #[tokio::test]
+    async fn never_resends_mutating_commands() {
+        let pool = pool(&[Outcome::FailBeforeWrite]);
+        let error = pool
+            .execute_once("run job=backup-web yes", OutputMode::Text)
+            .await
+            .unwrap_err();
+        assert!(error.downcast_ref::<NotSent>().is_some());
+        assert!(sent(&pool).is_empty());
+
+        let output = pool
+            .execute_once("run job=backup-web yes", OutputMode::Text)
+            .await
+            .unwrap();
+        assert_eq!(output, "ran run job=backup-web yes");
+    }
+}
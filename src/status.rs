@@ -0,0 +1,835 @@
+//! Parsers for the plain text `status` reports of the Bareos daemons.
+//!
+//! The reports are meant for humans: a banner with the daemon version, followed by sections
+//! like
+//!
+//! ```text
+//! Terminated Jobs:
+//!  JobId  Level      Files    Bytes   Status   Finished        Name
+//! ====================================================================
+//!    120  Incr         12    1.2 M   OK       16-Oct-26 21:00 backup-web
+//! ====
+//! ```
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Parsed `status director` report.
+#[derive(Debug, Default, Serialize)]
+pub struct DirectorStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daemon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started: Option<String>,
+    pub running: Vec<RunningJob>,
+    pub scheduled: Vec<ScheduledJob>,
+    pub terminated: Vec<TerminatedJob>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunningJob {
+    pub jobid: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    /// Unique job name, e.g. `backup-web.2026-10-17_10.00.00_03`.
+    pub job: String,
+    pub name: String,
+    pub status: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduledJob {
+    pub level: String,
+    #[serde(rename = "type")]
+    pub job_type: String,
+    pub priority: u32,
+    pub scheduled: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TerminatedJob {
+    pub jobid: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    pub files: u64,
+    /// Human readable size as printed by the daemon, e.g. `1.2 M`.
+    pub bytes: String,
+    pub status: String,
+    pub finished: String,
+    pub name: String,
+}
+
+/// Parsed `status client` or `status storage` report.
+#[derive(Debug, Default, Serialize)]
+pub struct DaemonStatus {
+    /// The client or storage resource that was queried.
+    pub resource: String,
+    pub reachable: bool,
+    /// Why the Director could not reach the daemon.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daemon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started: Option<String>,
+    pub running: Vec<DaemonJob>,
+    /// Only reported by storage daemons.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub devices: Option<Vec<Device>>,
+    pub terminated: Vec<TerminatedJob>,
+}
+
+/// A job running on a file or storage daemon.
+#[derive(Debug, Default, Serialize)]
+pub struct DaemonJob {
+    pub jobid: u64,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job: Option<String>,
+    /// `Writing` or `Reading` on a storage daemon.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processing_file: Option<String>,
+}
+
+/// A storage daemon device.
+#[derive(Debug, Default, Serialize)]
+pub struct Device {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// As reported, e.g. `is mounted with` or `is not open`.
+    pub state: String,
+    pub mounted: bool,
+    /// What the device is blocked on, e.g. waiting for a volume to be mounted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_bytes: Option<u64>,
+    /// Read/write position, e.g. `File=0 Block=1,234,567`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<String>,
+}
+
+fn running_job_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "jobid": { "type": "integer" },
+            "level": { "type": "string" },
+            "job": { "type": "string" },
+            "name": { "type": "string" },
+            "status": { "type": "string" }
+        },
+        "required": ["jobid", "job", "name", "status"]
+    })
+}
+
+fn terminated_job_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "jobid": { "type": "integer" },
+            "level": { "type": "string" },
+            "files": { "type": "integer" },
+            "bytes": { "type": "string" },
+            "status": { "type": "string" },
+            "finished": { "type": "string" },
+            "name": { "type": "string" }
+        },
+        "required": ["jobid", "files", "bytes", "status", "finished", "name"]
+    })
+}
+
+impl DirectorStatus {
+    pub fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "daemon": { "type": "string" },
+                "version": { "type": "string" },
+                "started": { "type": "string" },
+                "running": { "type": "array", "items": running_job_schema() },
+                "scheduled": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "level": { "type": "string" },
+                            "type": { "type": "string" },
+                            "priority": { "type": "integer" },
+                            "scheduled": { "type": "string" },
+                            "name": { "type": "string" },
+                            "volume": { "type": "string" },
+                            "schedule": { "type": "string" }
+                        },
+                        "required": ["level", "type", "priority", "scheduled", "name"]
+                    }
+                },
+                "terminated": { "type": "array", "items": terminated_job_schema() }
+            },
+            "required": ["running", "scheduled", "terminated"]
+        })
+    }
+
+    pub fn parse(output: &str) -> Self {
+        let (daemon, version) = banner(output);
+        let scheduled = match section(output, "Scheduled Jobs:") {
+            Some((header, rows)) => {
+                let last_column = header.split_whitespace().last().unwrap_or("");
+                rows.iter()
+                    .filter_map(|row| scheduled_job(row, last_column))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        DirectorStatus {
+            daemon,
+            version,
+            started: started(output),
+            running: rows(output, "Running Jobs:", running_job),
+            scheduled,
+            terminated: rows(output, "Terminated Jobs:", terminated_job),
+        }
+    }
+}
+
+impl DaemonStatus {
+    pub fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "resource": { "type": "string" },
+                "reachable": { "type": "boolean" },
+                "error": { "type": "string" },
+                "daemon": { "type": "string" },
+                "version": { "type": "string" },
+                "started": { "type": "string" },
+                "running": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "jobid": { "type": "integer" },
+                            "name": { "type": "string" },
+                            "job": { "type": "string" },
+                            "mode": { "type": "string" },
+                            "level": { "type": "string" },
+                            "volume": { "type": "string" },
+                            "pool": { "type": "string" },
+                            "device": { "type": "string" },
+                            "files": { "type": "integer" },
+                            "bytes": { "type": "integer" },
+                            "processing_file": { "type": "string" }
+                        },
+                        "required": ["jobid", "name"]
+                    }
+                },
+                "devices": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string" },
+                            "path": { "type": "string" },
+                            "state": { "type": "string" },
+                            "mounted": { "type": "boolean" },
+                            "blocked": { "type": "string" },
+                            "volume": { "type": "string" },
+                            "pool": { "type": "string" },
+                            "media_type": { "type": "string" },
+                            "total_bytes": { "type": "integer" },
+                            "position": { "type": "string" }
+                        },
+                        "required": ["name", "state", "mounted"]
+                    }
+                },
+                "terminated": { "type": "array", "items": terminated_job_schema() }
+            },
+            "required": ["resource", "reachable", "running", "terminated"]
+        })
+    }
+
+    pub fn parse_client(resource: &str, output: &str) -> Self {
+        Self::parse(resource, output, false)
+    }
+
+    pub fn parse_storage(resource: &str, output: &str) -> Self {
+        Self::parse(resource, output, true)
+    }
+
+    fn parse(resource: &str, output: &str, has_devices: bool) -> Self {
+        let (daemon, version) = banner(output);
+
+        // The Director prints this instead of the report when the daemon does not answer
+        let failure = output
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("Failed to connect to"));
+        if failure.is_some() || daemon.is_none() {
+            let error = failure
+                .or_else(|| output.lines().map(str::trim).find(|line| !line.is_empty()))
+                .unwrap_or("No status report received")
+                .to_string();
+            return DaemonStatus {
+                resource: resource.to_string(),
+                reachable: false,
+                error: Some(error),
+                ..Default::default()
+            };
+        }
+
+        DaemonStatus {
+            resource: resource.to_string(),
+            reachable: true,
+            error: None,
+            daemon,
+            version,
+            started: started(output),
+            running: daemon_jobs(&block(output, "Running Jobs:")),
+            devices: has_devices.then(|| devices(&block(output, "Device status:"))),
+            terminated: rows(output, "Terminated Jobs:", terminated_job),
+        }
+    }
+}
+
+// "bareos-dir Version: 23.0.1 (21 December 2023) Debian GNU/Linux 12"
+fn banner(output: &str) -> (Option<String>, Option<String>) {
+    output
+        .lines()
+        .find_map(|line| {
+            let (daemon, rest) = line.split_once(" Version: ")?;
+            let version = rest.split_whitespace().next()?;
+            Some((Some(daemon.trim().to_string()), Some(version.to_string())))
+        })
+        .unwrap_or_default()
+}
+
+// "Daemon started 10-Oct-26 08:00. Jobs: run=5, running=1"
+fn started(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let rest = line.trim().strip_prefix("Daemon started ")?;
+        let end = rest.find(". ").unwrap_or(rest.len());
+        Some(rest[..end].trim_end_matches('.').to_string())
+    })
+}
+
+/// The table header and rows of the section starting with the line `title`. Rows are the
+/// lines between the `====` underline of the header and the closing `====` or blank line.
+fn section<'a>(output: &'a str, title: &str) -> Option<(&'a str, Vec<&'a str>)> {
+    let mut lines = output
+        .lines()
+        .skip_while(|line| line.trim() != title)
+        .skip(1);
+
+    let mut header = "";
+    for line in lines.by_ref() {
+        if line.starts_with("====") {
+            break;
+        }
+        // "No Terminated Jobs." and friends end an empty section right away
+        if line.trim().is_empty() || line.trim_start().starts_with("No ") {
+            return Some((header, Vec::new()));
+        }
+        header = line;
+    }
+
+    let rows = lines
+        .take_while(|line| !line.starts_with("====") && !line.trim().is_empty())
+        .collect();
+    Some((header, rows))
+}
+
+fn rows<T>(output: &str, title: &str, parse: impl Fn(&str) -> Option<T>) -> Vec<T> {
+    section(output, title)
+        .map(|(_, rows)| rows.into_iter().filter_map(parse).collect())
+        .unwrap_or_default()
+}
+
+// The lines of an untabulated section, up to its closing `====`
+fn block<'a>(output: &'a str, title: &str) -> Vec<&'a str> {
+    output
+        .lines()
+        .skip_while(|line| line.trim() != title)
+        .skip(1)
+        .take_while(|line| line.trim() != "====")
+        .collect()
+}
+
+// The value of `key=value` or `key="quoted value"` in a status line
+fn value(line: &str, key: &str) -> Option<String> {
+    let pattern = format!("{}=", key);
+    let start = line
+        .match_indices(&pattern)
+        .find(|(index, _)| *index == 0 || line.as_bytes()[index - 1].is_ascii_whitespace())
+        .map(|(index, _)| index + pattern.len())?;
+    let rest = &line[start..];
+    match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().map(str::to_string),
+        // Numbers carry thousands separators, so only a trailing comma ends the value
+        None => rest
+            .split_whitespace()
+            .next()
+            .map(|value| value.trim_end_matches(',').to_string()),
+    }
+}
+
+// A job starts on an unindented line mentioning its JobId; its details are indented below:
+//
+//   JobId 123 Job backup-web.2026-10-17_10.00.00_03 is running.        (file daemon)
+//   Writing: Full Backup job backup-web JobId=123 Volume="Full-0001"   (storage daemon)
+fn daemon_jobs(lines: &[&str]) -> Vec<DaemonJob> {
+    let mut jobs: Vec<DaemonJob> = Vec::new();
+    let mut current = false;
+
+    for line in lines {
+        let indented = line.starts_with(char::is_whitespace);
+        if !indented {
+            current = false;
+            if let Some(job) = daemon_job(line) {
+                jobs.push(job);
+                current = true;
+            }
+            continue;
+        }
+        let Some(job) = jobs.last_mut().filter(|_| current) else {
+            continue;
+        };
+
+        let line = line.trim();
+        if let Some(file) = line.strip_prefix("Processing file: ") {
+            job.processing_file = Some(file.to_string());
+        }
+        if let Some((level, _)) = line.split_once(" Job started: ") {
+            job.level.get_or_insert_with(|| {
+                level.split_whitespace().next().unwrap_or(level).to_string()
+            });
+        }
+        job.files = job
+            .files
+            .or_else(|| value(line, "Files").and_then(|v| number(&v)));
+        job.bytes = job
+            .bytes
+            .or_else(|| value(line, "Bytes").and_then(|v| number(&v)));
+        job.pool = job.pool.take().or_else(|| value(line, "pool"));
+        job.device = job.device.take().or_else(|| value(line, "device"));
+    }
+    jobs
+}
+
+fn daemon_job(line: &str) -> Option<DaemonJob> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+
+    if tokens.first() == Some(&"JobId") {
+        let jobid = number(tokens.get(1)?)?;
+        let job = tokens
+            .iter()
+            .position(|token| *token == "Job")
+            .and_then(|index| tokens.get(index + 1))?;
+        let name = match unique_job_suffix(job) {
+            Some(end) => job[..end].to_string(),
+            None => job.to_string(),
+        };
+        return Some(DaemonJob {
+            jobid,
+            name,
+            job: Some(job.to_string()),
+            ..Default::default()
+        });
+    }
+
+    let jobid = number(&value(line, "JobId")?)?;
+    let (mode, rest) = line.split_once(": ")?;
+    let rest: Vec<&str> = rest.split_whitespace().collect();
+    let name = rest
+        .iter()
+        .position(|token| *token == "job")
+        .and_then(|index| rest.get(index + 1))?;
+    Some(DaemonJob {
+        jobid,
+        name: name.to_string(),
+        mode: Some(mode.to_string()),
+        level: rest.first().map(|level| level.to_string()),
+        volume: value(line, "Volume"),
+        ..Default::default()
+    })
+}
+
+// Devices are separated by `==` lines; autochanger and other headings are skipped
+fn devices(lines: &[&str]) -> Vec<Device> {
+    let mut devices: Vec<Device> = Vec::new();
+    let mut current = false;
+
+    for line in lines {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("Device \"") {
+            let Some((name, rest)) = rest.split_once('"') else {
+                continue;
+            };
+            let rest = rest.trim();
+            let (path, state) = match rest.strip_prefix('(').and_then(|rest| rest.split_once(')')) {
+                Some((path, state)) => (Some(path.to_string()), state),
+                None => (None, rest),
+            };
+            let state = state.trim().trim_end_matches([':', '.']).to_string();
+            devices.push(Device {
+                name: name.to_string(),
+                path,
+                mounted: state.starts_with("is mounted"),
+                state,
+                ..Default::default()
+            });
+            current = true;
+            continue;
+        }
+        if line.starts_with("==") {
+            current = false;
+            continue;
+        }
+        let Some(device) = devices.last_mut().filter(|_| current) else {
+            continue;
+        };
+
+        if line.contains("BLOCKED") {
+            device.blocked = Some(line.trim_end_matches(',').to_string());
+        } else if let Some(volume) = line.strip_prefix("Volume:") {
+            device.volume = Some(volume.trim().to_string());
+        } else if let Some(pool) = line.strip_prefix("Pool:") {
+            device.pool = Some(pool.trim().to_string());
+        } else if let Some(media_type) = line.strip_prefix("Media type:") {
+            device.media_type = Some(media_type.trim().to_string());
+        } else if let Some(position) = line.strip_prefix("Positioned at ") {
+            device.position = Some(position.to_string());
+        } else if line.starts_with("Total Bytes") {
+            device.total_bytes = value(line, "Bytes").and_then(|bytes| number(&bytes));
+        }
+    }
+    devices
+}
+
+// Dates are printed as "16-Oct-26"
+fn is_date(token: &str) -> bool {
+    let bytes = token.as_bytes();
+    bytes.len() == 9
+        && bytes[2] == b'-'
+        && bytes[6] == b'-'
+        && bytes[3..6].iter().all(u8::is_ascii_alphabetic)
+}
+
+// Unique job names end in ".2026-10-17_10.00.00_03"
+fn unique_job_suffix(job: &str) -> Option<usize> {
+    let mut dot = job.len();
+    for _ in 0..3 {
+        dot = job[..dot].rfind('.')?;
+    }
+    let suffix = &job.as_bytes()[dot + 1..];
+    (suffix.len() == 22 && suffix[4] == b'-' && suffix[10] == b'_').then_some(dot)
+}
+
+fn number(token: &str) -> Option<u64> {
+    token.replace(',', "").parse().ok()
+}
+
+fn running_job(row: &str) -> Option<RunningJob> {
+    let tokens: Vec<&str> = row.split_whitespace().collect();
+    let jobid = number(tokens.first()?)?;
+    let position = tokens
+        .iter()
+        .position(|token| unique_job_suffix(token).is_some())?;
+    let job = tokens[position];
+
+    Some(RunningJob {
+        jobid,
+        level: (position > 1).then(|| tokens[1..position].join(" ")),
+        job: job.to_string(),
+        name: job[..unique_job_suffix(job)?].to_string(),
+        status: tokens[position + 1..].join(" "),
+    })
+}
+
+fn scheduled_job(row: &str, last_column: &str) -> Option<ScheduledJob> {
+    let tokens: Vec<&str> = row.split_whitespace().collect();
+    if tokens.len() < 6 || !is_date(tokens[3]) {
+        return None;
+    }
+
+    // The last column is the volume in older versions and the schedule in newer ones
+    let (name, last) = if tokens.len() > 6 {
+        (
+            tokens[5..tokens.len() - 1].join(" "),
+            tokens.last().copied(),
+        )
+    } else {
+        (tokens[5].to_string(), None)
+    };
+    let last = last.map(str::to_string);
+    let (volume, schedule) = match last_column {
+        "Schedule" => (None, last),
+        _ => (last, None),
+    };
+
+    Some(ScheduledJob {
+        level: tokens[0].to_string(),
+        job_type: tokens[1].to_string(),
+        priority: tokens[2].parse().ok()?,
+        scheduled: format!("{} {}", tokens[3], tokens[4]),
+        name,
+        volume,
+        schedule,
+    })
+}
+
+fn terminated_job(row: &str) -> Option<TerminatedJob> {
+    let tokens: Vec<&str> = row.split_whitespace().collect();
+    let jobid = number(tokens.first()?)?;
+    let date = tokens.iter().position(|token| is_date(token))?;
+    if date + 2 > tokens.len() {
+        return None;
+    }
+
+    // Restores and admin jobs have no level, so the files column may come first
+    let mut index = 1;
+    let level = match tokens.get(index) {
+        Some(token) if number(token).is_none() => {
+            index += 1;
+            Some(token.to_string())
+        }
+        _ => None,
+    };
+    let files = number(tokens.get(index)?)?;
+    index += 1;
+    let mut bytes = tokens.get(index)?.to_string();
+    index += 1;
+    if let Some(unit) = tokens
+        .get(index)
+        .filter(|unit| unit.len() == 1 && "KMGTPE".contains(**unit) && index < date)
+    {
+        bytes = format!("{} {}", bytes, unit);
+        index += 1;
+    }
+    if index >= date {
+        return None;
+    }
+
+    Some(TerminatedJob {
+        jobid,
+        level,
+        files,
+        bytes,
+        status: tokens[index..date].join(" "),
+        finished: format!("{} {}", tokens[date], tokens[date + 1]),
+        name: tokens[date + 2..].join(" "),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIRECTOR: &str = "\
+bareos-dir Version: 23.0.1 (21 December 2023) Debian GNU/Linux 12
+Daemon started 10-Oct-26 08:00. Jobs: run=5, running=1, db:postgresql, bareos.dir
+
+Scheduled Jobs:
+Level          Type     Pri  Scheduled          Job Name           Schedule
+===================================================================================
+Incremental    Backup    10  18-Oct-26 21:00    backup-web         WeeklyCycle
+Full           Backup    11  18-Oct-26 23:10    BackupCatalog      WeeklyCycleAfterBackup
+====
+
+Running Jobs:
+Console connected at 17-Oct-26 10:00
+ JobId  Level   Name                       Status
+======================================================================
+   123  Full    backup-web.2026-10-17_10.00.00_03 is running
+   124          RestoreFiles.2026-10-17_10.05.00_04 is waiting on Storage \"File\"
+====
+
+Terminated Jobs:
+ JobId  Level      Files    Bytes   Status   Finished        Name
+====================================================================
+   120  Incr         12    1.2 M   OK       16-Oct-26 21:00 backup-web
+   121  Full          0        0   Error    16-Oct-26 23:10 BackupCatalog
+   122             3,456   12.5 G   OK -- with warnings 17-Oct-26 02:00 RestoreFiles
+====
+";
+
+    #[test]
+    fn parses_director_status() {
+        let status = DirectorStatus::parse(DIRECTOR);
+        assert_eq!(status.daemon.as_deref(), Some("bareos-dir"));
+        assert_eq!(status.version.as_deref(), Some("23.0.1"));
+        assert_eq!(status.started.as_deref(), Some("10-Oct-26 08:00"));
+
+        assert_eq!(status.scheduled.len(), 2);
+        assert_eq!(status.scheduled[0].name, "backup-web");
+        assert_eq!(status.scheduled[0].scheduled, "18-Oct-26 21:00");
+        assert_eq!(status.scheduled[0].schedule.as_deref(), Some("WeeklyCycle"));
+        assert_eq!(status.scheduled[1].priority, 11);
+
+        assert_eq!(status.running.len(), 2);
+        assert_eq!(status.running[0].name, "backup-web");
+        assert_eq!(status.running[0].level.as_deref(), Some("Full"));
+        assert_eq!(status.running[0].status, "is running");
+        assert_eq!(status.running[1].level, None);
+
+        assert_eq!(status.terminated.len(), 3);
+        assert_eq!(status.terminated[0].bytes, "1.2 M");
+        assert_eq!(status.terminated[1].status, "Error");
+        assert_eq!(status.terminated[2].level, None);
+        assert_eq!(status.terminated[2].files, 3456);
+        assert_eq!(status.terminated[2].status, "OK -- with warnings");
+        assert_eq!(status.terminated[2].name, "RestoreFiles");
+    }
+
+    const STORAGE: &str = "\
+Connecting to Storage daemon File at bareos:9103
+
+bareos-sd Version: 23.0.1 (21 December 2023) Debian GNU/Linux 12
+Daemon started 10-Oct-26 08:00. Jobs: run=5, running=1.
+
+Running Jobs:
+Writing: Full Backup job backup-web JobId=123 Volume=\"Full-0001\"
+    pool=\"Full\" device=\"FileStorage\" (/var/lib/bareos/storage)
+    spooling=0 despooling=0 despool_wait=0
+    Files=1,234 Bytes=5,678,901 AveBytes/sec=1,000 LastBytes/sec=2,000
+====
+
+Terminated Jobs:
+ JobId  Level    Files      Bytes   Status   Finished        Name
+===================================================================
+   120  Incr         12    1.2 M   OK       16-Oct-26 21:00 backup-web
+====
+
+Device status:
+
+Device \"FileStorage\" (/var/lib/bareos/storage) is mounted with:
+    Volume:      Full-0001
+    Pool:        Full
+    Media type:  File
+    Total Bytes=1,234,567 Blocks=19 Bytes/block=64,977
+    Positioned at File=0 Block=1,234,567
+==
+Device \"Tape0\" (/dev/nst0) is not open.
+    Device is BLOCKED waiting for mount of volume \"Tape-0003\",
+       Pool:        Full
+       Media type:  LTO
+==
+====
+";
+
+    #[test]
+    fn parses_storage_status() {
+        let status = DaemonStatus::parse_storage("File", STORAGE);
+        assert!(status.reachable);
+        assert_eq!(status.daemon.as_deref(), Some("bareos-sd"));
+
+        assert_eq!(status.running.len(), 1);
+        let job = &status.running[0];
+        assert_eq!(job.jobid, 123);
+        assert_eq!(job.name, "backup-web");
+        assert_eq!(job.mode.as_deref(), Some("Writing"));
+        assert_eq!(job.volume.as_deref(), Some("Full-0001"));
+        assert_eq!(job.device.as_deref(), Some("FileStorage"));
+        assert_eq!(job.bytes, Some(5678901));
+
+        let devices = status.devices.unwrap();
+        assert_eq!(devices.len(), 2);
+        assert!(devices[0].mounted);
+        assert_eq!(devices[0].volume.as_deref(), Some("Full-0001"));
+        assert_eq!(devices[0].total_bytes, Some(1234567));
+        assert_eq!(
+            devices[0].position.as_deref(),
+            Some("File=0 Block=1,234,567")
+        );
+        assert!(!devices[1].mounted);
+        assert_eq!(devices[1].state, "is not open");
+        assert_eq!(
+            devices[1].blocked.as_deref(),
+            Some("Device is BLOCKED waiting for mount of volume \"Tape-0003\"")
+        );
+
+        assert_eq!(status.terminated.len(), 1);
+    }
+
+    #[test]
+    fn parses_client_status() {
+        let status = DaemonStatus::parse_client(
+            "web01-fd",
+            "\
+Connecting to Client web01-fd at web01:9102
+
+web01-fd Version: 23.0.1 (21 December 2023)  Debian GNU/Linux 12
+Daemon started 10-Oct-26 08:01. Jobs: run=12 running=1, bareos.fd
+
+Running Jobs:
+JobId 123 Job backup-web.2026-10-17_10.00.00_03 is running.
+    Full Backup Job started: 17-Oct-26 10:00
+    Files=1,234 Bytes=5,678,901 AveBytes/sec=1,000 LastBytes/sec=2,000 Errors=0
+    Processing file: /var/www/index.html
+Director connected at: 17-Oct-26 10:00
+====
+",
+        );
+        assert!(status.reachable);
+        assert!(status.devices.is_none());
+        let job = &status.running[0];
+        assert_eq!(job.name, "backup-web");
+        assert_eq!(job.level.as_deref(), Some("Full"));
+        assert_eq!(job.files, Some(1234));
+        assert_eq!(job.processing_file.as_deref(), Some("/var/www/index.html"));
+    }
+
+    #[test]
+    fn reports_unreachable_daemons() {
+        let status = DaemonStatus::parse_client(
+            "web01-fd",
+            "Connecting to Client web01-fd at web01:9102\nFailed to connect to Client web01-fd.\n====\n",
+        );
+        assert!(!status.reachable);
+        assert_eq!(
+            status.error.as_deref(),
+            Some("Failed to connect to Client web01-fd.")
+        );
+    }
+
+    #[test]
+    fn parses_empty_sections() {
+        let status = DirectorStatus::parse(
+            "Scheduled Jobs:\nNo Scheduled Jobs.\n====\n\nRunning Jobs:\nNo Jobs running.\n====\n",
+        );
+        assert!(status.scheduled.is_empty());
+        assert!(status.running.is_empty());
+        assert!(status.terminated.is_empty());
+        assert_eq!(status.version, None);
+    }
+}
@@ -0,0 +1,66 @@
+//! Newline-delimited JSON-RPC over stdin/stdout.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::io::{self, Write};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::{mpsc, Semaphore};
+use tracing::error;
+
+use crate::server::Server;
+
+/// Serves MCP on stdin/stdout until stdin is closed, running up to `max_concurrent`
+/// requests at a time.
+pub async fn serve(server: Arc<Server>, max_concurrent: usize) -> Result<()> {
+    let permits = Arc::new(Semaphore::new(max_concurrent.max(1)));
+
+    // Responses complete out of order; a single writer keeps each one on its own line
+    let (responses, mut outgoing) = mpsc::unbounded_channel::<Value>();
+    let writer = tokio::task::spawn_blocking(move || {
+        let mut stdout = io::stdout();
+        while let Some(response) = outgoing.blocking_recv() {
+            writeln!(stdout, "{}", serde_json::to_string(&response)?)?;
+            stdout.flush()?;
+        }
+        Ok::<_, anyhow::Error>(())
+    });
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request: Value = match serde_json::from_str(&line) {
+            Ok(req) => req,
+            Err(e) => {
+                error!("Failed to parse request: {}", e);
+                continue;
+            }
+        };
+
+        // Handled inline so a cancellation is never stuck behind the requests it cancels
+        if request["method"] == "notifications/cancelled" {
+            server.handle_request(request).await;
+            continue;
+        }
+
+        // Registered before spawning, so a cancellation on the next line already finds it
+        let registered = server.register_request("", request);
+        let server = Arc::clone(&server);
+        let permits = Arc::clone(&permits);
+        let responses = responses.clone();
+        tokio::spawn(async move {
+            let permit = permits.acquire();
+            if let Some(response) = server.handle_registered_request(registered, permit).await {
+                let _ = responses.send(response);
+            }
+        });
+    }
+
+    // Let in-flight requests finish and their responses be written before exiting
+    drop(responses);
+    writer.await??;
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! Test doubles shared by the unit tests.
+
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use crate::bareos::BareosClient;
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+// Answers `@echo` like bconsole and sources `cases.sh` from its own directory for
+// everything else, logging each command to `commands.log`
+const SCRIPT: &str = r#"#!/bin/sh
+dir=$(dirname "$0")
+while IFS= read -r line; do
+  case "$line" in
+    "@echo "*) printf '%s\n' "${line#@echo }" ;;
+    *) printf '%s\n' "$line" >> "$dir/commands.log"; . "$dir/cases.sh" ;;
+  esac
+done
+"#;
+
+// Written once and linked into every fake's directory: executing a file another test
+// thread is still writing fails with ETXTBSY
+fn script() -> &'static PathBuf {
+    static SCRIPT_PATH: OnceLock<PathBuf> = OnceLock::new();
+    SCRIPT_PATH.get_or_init(|| {
+        let path = std::env::temp_dir().join(format!("bareos-mcp-fake-{}.sh", std::process::id()));
+        std::fs::write(&path, SCRIPT).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    })
+}
+
+/// A shell script standing in for bconsole.
+pub(crate) struct FakeBconsole {
+    dir: PathBuf,
+}
+
+impl FakeBconsole {
+    /// `cases` are `case` branches matched against each command line, e.g.
+    /// `".defaults job=backup-web") echo client=web01-fd ;;`. Unmatched commands print nothing.
+    pub(crate) fn new(cases: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "bareos-mcp-fake-{}-{}",
+            std::process::id(),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("cases.sh"),
+            format!("case \"$line\" in\n{}\n  *) ;;\nesac\n", cases),
+        )
+        .unwrap();
+        symlink(script(), dir.join("bconsole")).unwrap();
+        Self { dir }
+    }
+
+    pub(crate) fn client(&self) -> BareosClient {
+        BareosClient::bconsole(self.dir.join("bconsole").to_str().unwrap(), 1)
+    }
+
+    /// The directory holding the script, where `cases` may keep state between commands.
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Every command the fake received, in order, across all of its sessions.
+    pub(crate) fn commands(&self) -> Vec<String> {
+        std::fs::read_to_string(self.dir.join("commands.log"))
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.starts_with(".api"))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl Drop for FakeBconsole {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
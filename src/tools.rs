@@ -0,0 +1,471 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::bareos::{BareosClient, JobListParams};
+use crate::confirm::{Confirmations, Holder};
+use crate::model::{Client, FileSet, Job, JobLogEntry, Pool, Volume};
+use crate::status::{DaemonStatus, DirectorStatus};
+use crate::validate::Arguments;
+
+mod browse;
+mod chain;
+mod files;
+mod restore;
+mod write;
+
+// Resource types accepted by `show`; `director` has a single resource and ignores the name
+const RESOURCE_TYPES: &[&str] = &[
+    "director", "client", "job", "jobdefs", "schedule", "fileset", "pool", "storage", "messages",
+    "catalog", "console", "profile", "counter", "user",
+];
+
+/// What a tool produced; converted into an MCP `CallToolResult` by `into_result`.
+pub enum ToolOutput {
+    Text(String),
+    Structured(Value),
+}
+
+impl ToolOutput {
+    pub fn into_result(self) -> Value {
+        match self {
+            ToolOutput::Text(content) => json!({
+                "content": [
+                    {
+                        "type": "text",
+                        "text": content
+                    }
+                ]
+            }),
+            // Clients without structured content support fall back to the serialized JSON
+            ToolOutput::Structured(content) => json!({
+                "content": [
+                    {
+                        "type": "text",
+                        "text": content.to_string()
+                    }
+                ],
+                "structuredContent": content
+            }),
+        }
+    }
+}
+
+// Schema for list results: an object holding an array of catalog records under `key`
+fn list_output_schema(key: &str, items: Value) -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            key: {
+                "type": "array",
+                "items": items
+            }
+        },
+        "required": [key]
+    })
+}
+
+fn list_output<T: Serialize>(key: &str, records: Vec<T>) -> Result<ToolOutput> {
+    Ok(ToolOutput::Structured(json!({ key: records })))
+}
+
+/// Tool definitions as advertised by `tools/list`; write tools are only listed in write mode.
+pub fn definitions(write_mode: bool) -> Vec<Value> {
+    let mut tools = vec![
+        json!({
+            "name": "list_jobs",
+            "description": "List Bareos jobs with optional filters. Returns ALL job types by default (backup, restore, verify, admin, copy, migration). IMPORTANT: When users ask about 'backups' or 'backup performance', filter by jobtype='B' to show only backup jobs, excluding verification and other operations. COMBINATION RULES: All filter parameters (job, client, jobstatus, jobtype, joblevel, volume, pool) can be freely combined with each other and with time/output parameters. Time parameters (days, hours) are mutually exclusive - if both provided, hours wins. Output parameters (last, count) are mutually exclusive - if both provided, count wins. Examples: {jobtype:'B',hours:24}, {client:'web',days:7}, {jobstatus:'f',hours:24,count:true}",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "job": {
+                        "type": "string",
+                        "description": "Filter by job name. Can combine with any other parameters."
+                    },
+                    "client": {
+                        "type": "string",
+                        "description": "Filter by client name. Can combine with any other parameters."
+                    },
+                    "jobstatus": {
+                        "type": "string",
+                        "description": "Filter by job status: T (terminated/success), f (failed), R (running), C (created), A (canceled), E (error). Can combine with any other parameters."
+                    },
+                    "jobtype": {
+                        "type": "string",
+                        "description": "Filter by job type: B (backup), R (restore), V (verify), D (admin), C (copy), M (migration). Can combine with any other parameters."
+                    },
+                    "joblevel": {
+                        "type": "string",
+                        "description": "Filter by job level: F (full), I (incremental), D (differential). Can combine with any other parameters."
+                    },
+                    "volume": {
+                        "type": "string",
+                        "description": "Filter by volume name. Can combine with any other parameters."
+                    },
+                    "pool": {
+                        "type": "string",
+                        "description": "Filter by pool name. Can combine with any other parameters."
+                    },
+                    "days": {
+                        "type": "number",
+                        "description": "Show jobs from last N days. Mutually exclusive with hours (hours wins if both given). Can combine with all filter and output parameters."
+                    },
+                    "hours": {
+                        "type": "number",
+                        "description": "Show jobs from last N hours. Mutually exclusive with days (this takes precedence if both given). Can combine with all filter and output parameters."
+                    },
+                    "last": {
+                        "type": "boolean",
+                        "description": "Show only the most recent run of each job (within the filter criteria). WARNING: If jobs ran multiple times in the time range, only the LAST run will be returned. Mutually exclusive with count (count wins if both given). Can combine with all filter and time parameters."
+                    },
+                    "count": {
+                        "type": "boolean",
+                        "description": "Show count of matching jobs instead of job details. Mutually exclusive with last (this takes precedence if both given). Can combine with all filter and time parameters."
+                    }
+                }
+            },
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "jobs": {
+                        "type": "array",
+                        "items": Job::schema()
+                    },
+                    "count": {
+                        "type": "integer"
+                    }
+                }
+            }
+        }),
+        json!({
+            "name": "get_job_status",
+            "description": "Get detailed status of a specific job by ID",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "job_id": {
+                        "type": "string",
+                        "description": "The job ID to query"
+                    }
+                },
+                "required": ["job_id"]
+            },
+            "outputSchema": list_output_schema("jobs", Job::schema())
+        }),
+        json!({
+            "name": "get_job_log",
+            "description": "Get the log output for a specific job",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "job_id": {
+                        "type": "string",
+                        "description": "The job ID to get logs for"
+                    }
+                },
+                "required": ["job_id"]
+            },
+            "outputSchema": list_output_schema("joblog", JobLogEntry::schema())
+        }),
+        json!({
+            "name": "list_clients",
+            "description": "List all Bareos clients (file daemons)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {}
+            },
+            "outputSchema": list_output_schema("clients", Client::schema())
+        }),
+        json!({
+            "name": "list_filesets",
+            "description": "List all configured filesets",
+            "inputSchema": {
+                "type": "object",
+                "properties": {}
+            },
+            "outputSchema": list_output_schema("filesets", FileSet::schema())
+        }),
+        json!({
+            "name": "list_pools",
+            "description": "List all storage pools",
+            "inputSchema": {
+                "type": "object",
+                "properties": {}
+            },
+            "outputSchema": list_output_schema("pools", Pool::schema())
+        }),
+        json!({
+            "name": "list_volumes",
+            "description": "List all volumes/media in storage",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "pool": {
+                        "type": "string",
+                        "description": "Filter by specific pool name (optional)"
+                    }
+                }
+            },
+            "outputSchema": list_output_schema("volumes", Volume::schema())
+        }),
+        files::definition(),
+        json!({
+            "name": "show_job",
+            "description": "Show the configured resource definition for a Bareos job. Returns the director's job resource properties including: enabled, schedule, client, fileset, storage, pool, level, type, priority, and more. Use this to inspect job configuration rather than runtime/historical data.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "job_name": {
+                        "type": "string",
+                        "description": "The job name to show configuration for"
+                    }
+                },
+                "required": ["job_name"]
+            }
+        }),
+        json!({
+            "name": "show_jobdefs",
+            "description": "Show the configured JobDefs resource definition. JobDefs are templates that jobs inherit from, defining default values for type, level, client, fileset, schedule, storage, pool, messages, priority, etc.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "jobdefs_name": {
+                        "type": "string",
+                        "description": "The JobDefs name to show configuration for"
+                    }
+                },
+                "required": ["jobdefs_name"]
+            }
+        }),
+        json!({
+            "name": "show_schedule",
+            "description": "Show the configured Schedule resource definition. Returns the run directives that define when jobs are executed, including timing, level overrides, and pool overrides.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "schedule_name": {
+                        "type": "string",
+                        "description": "The Schedule name to show configuration for"
+                    }
+                },
+                "required": ["schedule_name"]
+            }
+        }),
+        json!({
+            "name": "show_resource",
+            "description": "Show the configured definition of any Director resource: director, client, job, jobdefs, schedule, fileset, pool, storage, messages, catalog, console, profile, counter or user. Use this to find out why a job backed up the wrong paths (fileset) or went to the wrong pool or storage. Passwords, PSKs and keys are redacted.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "resource_type": {
+                        "type": "string",
+                        "enum": RESOURCE_TYPES,
+                        "description": "The kind of resource to show"
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "The resource name; omit to show all resources of the type"
+                    }
+                },
+                "required": ["resource_type"]
+            }
+        }),
+        json!({
+            "name": "status_director",
+            "description": "Show what the Director is doing right now: running jobs, jobs scheduled to start within the next days, recently terminated jobs, and the Director's version and start time. Use this to answer 'what is running' or 'what runs tonight'; use list_jobs for older history.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "days": {
+                        "type": "number",
+                        "description": "Show jobs scheduled within the next N days (default 1)"
+                    }
+                }
+            },
+            "outputSchema": DirectorStatus::schema()
+        }),
+        json!({
+            "name": "status_client",
+            "description": "Contact a client's file daemon through the Director and report whether it is reachable, its version, the jobs running on it and its recently terminated jobs. An unreachable client is reported with reachable=false and the Director's error instead of failing. Start here when a backup failed with a connection error.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "client": {
+                        "type": "string",
+                        "description": "The client name as configured in the Director"
+                    }
+                },
+                "required": ["client"]
+            },
+            "outputSchema": DaemonStatus::schema()
+        }),
+        json!({
+            "name": "status_storage",
+            "description": "Contact a storage daemon through the Director and report whether it is reachable, its version, running jobs, device states (mounted volume, blocked waiting for media, read/write position) and recently terminated jobs. An unreachable storage daemon is reported with reachable=false instead of failing.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "storage": {
+                        "type": "string",
+                        "description": "The storage name as configured in the Director"
+                    }
+                },
+                "required": ["storage"]
+            },
+            "outputSchema": DaemonStatus::schema()
+        }),
+    ];
+    tools.extend(browse::definitions());
+    tools.extend(restore::definitions());
+    tools.push(chain::definition());
+
+    if write_mode {
+        tools.extend(write::definitions());
+    }
+    tools
+}
+
+/// Runs the named tool with the `arguments` object of a `tools/call` request.
+///
+/// Write tools go through `confirmations`: a call without a token returns a preview and a
+/// token, and only a second call by the same `holder` presenting it executes the command.
+pub async fn call(
+    client: &BareosClient,
+    confirmations: &Confirmations,
+    holder: &Holder,
+    name: &str,
+    arguments: &Value,
+) -> Result<ToolOutput> {
+    let arguments = Arguments::new(arguments)?;
+
+    if write::is_write_tool(name) {
+        if !client.write_mode() {
+            anyhow::bail!(
+                "Tool '{}' changes Director state and is only available when the server runs with BAREOS_WRITE_MODE=true",
+                name
+            );
+        }
+        return write::call(client, confirmations, holder, name, &arguments).await;
+    }
+
+    match name {
+        "list_jobs" => {
+            // Pass all parameters through - bconsole handles precedence
+            let params = JobListParams {
+                job: arguments.optional_name("job")?,
+                client: arguments.optional_name("client")?,
+                jobstatus: arguments.optional_job_status("jobstatus")?,
+                jobtype: arguments.optional_job_type("jobtype")?,
+                joblevel: arguments.optional_job_level("joblevel")?,
+                volume: arguments.optional_name("volume")?,
+                pool: arguments.optional_name("pool")?,
+                days: arguments.optional_u32("days")?,
+                hours: arguments.optional_u32("hours")?,
+                last: arguments.flag("last")?,
+                count: arguments.flag("count")?,
+                ..Default::default()
+            };
+
+            if params.count {
+                client
+                    .count_jobs(params)
+                    .await
+                    .map(|count| ToolOutput::Structured(json!({ "count": count })))
+            } else {
+                client
+                    .list_jobs(params)
+                    .await
+                    .and_then(|records| list_output("jobs", records))
+            }
+        }
+        "get_job_status" => {
+            let job_id = arguments.job_id("job_id")?;
+            client
+                .get_job_status(job_id)
+                .await
+                .and_then(|records| list_output("jobs", records))
+        }
+        "get_job_log" => {
+            let job_id = arguments.job_id("job_id")?;
+            client
+                .get_job_log(job_id)
+                .await
+                .and_then(|records| list_output("joblog", records))
+        }
+        "list_clients" => client
+            .list_clients()
+            .await
+            .and_then(|records| list_output("clients", records)),
+        "list_filesets" => client
+            .list_filesets()
+            .await
+            .and_then(|records| list_output("filesets", records)),
+        "list_pools" => client
+            .list_pools()
+            .await
+            .and_then(|records| list_output("pools", records)),
+        "list_volumes" => {
+            let pool = arguments.optional_name("pool")?;
+            client
+                .list_volumes(pool.as_deref())
+                .await
+                .and_then(|records| list_output("volumes", records))
+        }
+        "list_files" => files::call(client, &arguments).await,
+        "browse_backup" | "list_file_versions" => browse::call(client, name, &arguments).await,
+        "plan_restore" => restore::call(client, name, &arguments).await,
+        "analyze_job_chain" => chain::call(client, &arguments).await,
+        "show_job" => {
+            let job_name = arguments.name("job_name")?;
+            client.show_job(&job_name).await.map(ToolOutput::Text)
+        }
+        "show_jobdefs" => {
+            let jobdefs_name = arguments.name("jobdefs_name")?;
+            client
+                .show_jobdefs(&jobdefs_name)
+                .await
+                .map(ToolOutput::Text)
+        }
+        "show_schedule" => {
+            let schedule_name = arguments.name("schedule_name")?;
+            client
+                .show_schedule(&schedule_name)
+                .await
+                .map(ToolOutput::Text)
+        }
+        "status_director" => {
+            let days = arguments.optional_u32("days")?;
+            let status = client.status_director(days).await?;
+            Ok(ToolOutput::Structured(serde_json::to_value(status)?))
+        }
+        "show_resource" => {
+            let resource_type = arguments.choice("resource_type", RESOURCE_TYPES)?;
+            let name = match resource_type.as_str() {
+                "director" => None,
+                _ => arguments.optional_name("name")?,
+            };
+            client
+                .show(&resource_type, name.as_deref())
+                .await
+                .map(ToolOutput::Text)
+        }
+        // An unknown name would make the Director prompt for a selection
+        "status_client" => {
+            let name = arguments.name("client")?;
+            if !client.client_names().await?.contains(&name) {
+                anyhow::bail!("No client resource named '{}'", name);
+            }
+            let status = client.status_client(&name).await?;
+            Ok(ToolOutput::Structured(serde_json::to_value(status)?))
+        }
+        "status_storage" => {
+            let name = arguments.name("storage")?;
+            if !client.storage_names().await?.contains(&name) {
+                anyhow::bail!("No storage resource named '{}'", name);
+            }
+            let status = client.status_storage(&name).await?;
+            Ok(ToolOutput::Structured(serde_json::to_value(status)?))
+        }
+        _ => Err(anyhow::anyhow!("Unknown tool: {}", name)),
+    }
+}
@@ -0,0 +1,469 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::ToolOutput;
+use crate::bareos::{BareosClient, JobListParams};
+use crate::bvfs::BvfsEntry;
+use crate::chain::{self, is_successful};
+use crate::model::Job;
+use crate::validate::{Arguments, InvalidParams};
+
+const DEFAULT_LIMIT: u32 = 200;
+const MAX_LIMIT: u32 = 1000;
+
+/// How many days of a client's backups are listed (and for `list_file_versions` added to the
+/// BVFS cache), counted back from the requested date or from today.
+const HISTORY_DAYS: u32 = 365;
+
+pub fn definitions() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "browse_backup",
+            "description": "Browse a client's backed-up directory tree as it was restorable at a point in time: lists the subdirectories and files of one directory, merged from the most recent successful backup on or before the date and the Full/Differential backups it builds on. Start at path '/' and descend. Use list_file_versions to see every backed up version of one file.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "client": {
+                        "type": "string",
+                        "description": "The client whose backups to browse"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Directory to list (default '/'), e.g. /etc/nginx/"
+                    },
+                    "date": {
+                        "type": "string",
+                        "description": "Browse the state as of the end of this day (YYYY-MM-DD); defaults to the latest backup"
+                    },
+                    "fileset": {
+                        "type": "string",
+                        "description": "Only use backups of this FileSet; defaults to the FileSet of the latest backup"
+                    },
+                    "offset": {
+                        "type": "number",
+                        "description": "Number of directories and files to skip (default 0); use next_offset from the previous page"
+                    },
+                    "limit": {
+                        "type": "number",
+                        "description": "Maximum number of directories and of files to return (default 200, at most 1000)"
+                    }
+                },
+                "required": ["client"]
+            },
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "client": { "type": "string" },
+                    "path": { "type": "string" },
+                    "job": Job::schema(),
+                    "jobids": { "type": "array", "items": { "type": "integer" } },
+                    "directories": { "type": "array", "items": BvfsEntry::schema() },
+                    "files": { "type": "array", "items": BvfsEntry::schema() },
+                    "has_more": { "type": "boolean" },
+                    "next_offset": { "type": "integer" },
+                    "warnings": { "type": "array", "items": { "type": "string" } }
+                },
+                "required": ["client", "path", "job", "jobids", "directories", "files", "has_more", "warnings"]
+            }
+        }),
+        json!({
+            "name": "list_file_versions",
+            "description": "List every backed up version of one file of a client, newest first, with the job ID, backup time, level, file size, modification time, MD5 and the volume holding it. Only backups of the last 365 days are searched unless days says otherwise; warnings says when older backups were left out. Answers 'which versions of /etc/nginx/nginx.conf do we have and from which job'.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "client": {
+                        "type": "string",
+                        "description": "The client the file was backed up from"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Full path of the file, e.g. /etc/nginx/nginx.conf"
+                    },
+                    "fileset": {
+                        "type": "string",
+                        "description": "Only list versions backed up with this FileSet"
+                    },
+                    "days": {
+                        "type": "number",
+                        "description": "Search the backups of the last N days (default 365)"
+                    }
+                },
+                "required": ["client", "path"]
+            },
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "client": { "type": "string" },
+                    "path": { "type": "string" },
+                    "versions": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "jobid": { "type": "integer" },
+                                "job": { "type": "string" },
+                                "level": { "type": "string" },
+                                "backup_time": { "type": "string" },
+                                "size": { "type": "integer" },
+                                "mtime": { "type": "string" },
+                                "md5": { "type": "string" },
+                                "volume": { "type": "string" }
+                            },
+                            "required": ["jobid"]
+                        }
+                    },
+                    "history_days": { "type": "integer" },
+                    "warnings": { "type": "array", "items": { "type": "string" } }
+                },
+                "required": ["client", "path", "versions", "history_days", "warnings"]
+            }
+        }),
+    ]
+}
+
+fn required_path(arguments: &Arguments<'_>, name: &str) -> Result<String, InvalidParams> {
+    arguments.optional_path(name)?.ok_or_else(|| InvalidParams {
+        argument: name.to_string(),
+        reason: "is required".to_string(),
+    })
+}
+
+/// The successful backups of `client` of the last `days` days, oldest first; only those of
+/// `fileset` when given.
+///
+/// Uses `llist`, the only job listing that names the FileSet.
+pub(super) async fn successful_backups(
+    client: &BareosClient,
+    name: &str,
+    fileset: Option<&str>,
+    days: u32,
+) -> Result<Vec<Job>> {
+    let mut jobs: Vec<Job> = client
+        .list_jobs(JobListParams {
+            client: Some(name.to_string()),
+            jobtype: Some("B".to_string()),
+            days: Some(days),
+            long: true,
+            ..Default::default()
+        })
+        .await?
+        .into_iter()
+        .filter(is_successful)
+        .filter(|job| fileset.is_none_or(|fileset| job.fileset.as_deref() == Some(fileset)))
+        .collect();
+    jobs.sort_by(|a, b| chain::order(a).cmp(&chain::order(b)));
+    Ok(jobs)
+}
+
+// Whether `client` has backups from before the last `days` days
+async fn has_older_backups(client: &BareosClient, name: &str, days: u32) -> Result<bool> {
+    let backups = |days| JobListParams {
+        client: Some(name.to_string()),
+        jobtype: Some("B".to_string()),
+        days,
+        count: true,
+        ..Default::default()
+    };
+    Ok(client.count_jobs(backups(None)).await? > client.count_jobs(backups(Some(days))).await?)
+}
+
+/// The number of days of backups to list for a backup as of `date`: `HISTORY_DAYS` back from
+/// the date, or from today without one.
+pub(super) fn history_days(date: Option<&str>) -> u32 {
+    date.map_or(0, days_since).saturating_add(HISTORY_DAYS)
+}
+
+// Whole days from a validated YYYY-MM-DD date to today, 0 for dates ahead
+fn days_since(date: &str) -> u32 {
+    let field = |range: std::ops::Range<usize>| date[range].parse::<i64>().unwrap_or(0);
+    let (year, month, day) = (field(0..4), field(5..7), field(8..10));
+    // Howard Hinnant's days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let today = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() / 86400) as i64;
+    u32::try_from(today - days).unwrap_or(0)
+}
+
+// Catalog times are "YYYY-MM-DD HH:MM:SS", so they compare as strings
+fn latest_as_of<'a>(backups: &'a [Job], date: Option<&str>) -> Option<&'a Job> {
+    let end_of_day = date.map(|date| format!("{} 23:59:59", date));
+    backups
+        .iter()
+        .rev()
+        .find(|job| match (&end_of_day, &job.start_time) {
+            (Some(end), Some(start)) => start <= end,
+            (Some(_), None) => false,
+            (None, _) => true,
+        })
+}
+
+/// The most recent successful backup of `client` (of `fileset`, when given) that started on
+/// or before `date`, and a warning when the client has backups of other FileSets that were
+/// left out.
+pub(super) async fn backup_as_of(
+    client: &BareosClient,
+    name: &str,
+    fileset: Option<&str>,
+    date: Option<&str>,
+) -> Result<(Job, Vec<String>)> {
+    let days = history_days(date);
+    let backups = successful_backups(client, name, None, days).await?;
+    let candidates: Vec<Job> = backups
+        .iter()
+        .filter(|job| fileset.is_none_or(|fileset| job.fileset.as_deref() == Some(fileset)))
+        .cloned()
+        .collect();
+    let job = latest_as_of(&candidates, date).cloned().ok_or_else(|| {
+        let of = fileset
+            .map(|fileset| format!(" with FileSet '{}'", fileset))
+            .unwrap_or_default();
+        match date {
+            Some(date) => anyhow::anyhow!(
+                "No successful backup of client '{}'{} in the {} days up to {}",
+                name,
+                of,
+                HISTORY_DAYS,
+                date
+            ),
+            None => anyhow::anyhow!(
+                "No successful backup of client '{}'{} in the last {} days",
+                name,
+                of,
+                days
+            ),
+        }
+    })?;
+
+    let mut warnings = Vec::new();
+    if fileset.is_none() {
+        let others: BTreeSet<&str> = backups
+            .iter()
+            .filter_map(|other| other.fileset.as_deref())
+            .filter(|other| Some(*other) != job.fileset.as_deref())
+            .collect();
+        if !others.is_empty() {
+            warnings.push(format!(
+                "Client '{}' also has backups of FileSet {}; this uses FileSet '{}' of the latest backup, pass fileset to choose another",
+                name,
+                others.into_iter().collect::<Vec<_>>().join(", "),
+                job.fileset.as_deref().unwrap_or("unknown")
+            ));
+        }
+    }
+    Ok((job, warnings))
+}
+
+/// The jobs restoring `job` needs, with the BVFS cache built for them, and a warning when
+/// the Director could not work them out.
+pub(super) async fn restore_chain(
+    client: &BareosClient,
+    job: &Job,
+) -> Result<(Vec<u64>, Option<String>)> {
+    let mut job_ids = client.bvfs_get_jobids(job.job_id).await?;
+    let mut warning = None;
+    if job_ids.is_empty() {
+        warning = Some(format!(
+            "The Director found no backup chain for job {}; only its own files are included, so files from the Full and Differential it builds on are missing",
+            job.job_id
+        ));
+        job_ids.push(job.job_id);
+    }
+    client.bvfs_update(&job_ids).await?;
+    Ok((job_ids, warning))
+}
+
+// BVFS paths are directories ending in a slash
+fn directory(path: &str) -> String {
+    if path.ends_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/", path)
+    }
+}
+
+pub async fn call(
+    client: &BareosClient,
+    name: &str,
+    arguments: &Arguments<'_>,
+) -> Result<ToolOutput> {
+    match name {
+        "browse_backup" => {
+            let client_name = arguments.name("client")?;
+            let path = directory(&arguments.optional_path("path")?.unwrap_or_default());
+            let date = arguments.optional_date("date")?;
+            let fileset = arguments.optional_name("fileset")?;
+            let offset = arguments.optional_u32("offset")?.unwrap_or(0) as usize;
+            let limit = arguments
+                .optional_u32("limit")?
+                .unwrap_or(DEFAULT_LIMIT)
+                .clamp(1, MAX_LIMIT) as usize;
+
+            let (job, mut warnings) =
+                backup_as_of(client, &client_name, fileset.as_deref(), date.as_deref()).await?;
+            let (job_ids, warning) = restore_chain(client, &job).await?;
+            warnings.extend(warning);
+            let directories = client.bvfs_lsdirs(&job_ids, &path, offset, limit).await?;
+            let files = client.bvfs_lsfiles(&job_ids, &path, offset, limit).await?;
+
+            let has_more = directories.len() == limit || files.len() == limit;
+            let directories: Vec<BvfsEntry> = directories
+                .into_iter()
+                .filter(|entry| entry.name != "." && entry.name != "..")
+                .collect();
+            let mut content = json!({
+                "client": client_name,
+                "path": path,
+                "job": job,
+                "jobids": job_ids,
+                "directories": directories,
+                "files": files,
+                "has_more": has_more,
+                "warnings": warnings
+            });
+            if has_more {
+                content["next_offset"] = json!(offset + limit);
+            }
+            Ok(ToolOutput::Structured(content))
+        }
+        "list_file_versions" => {
+            let client_name = arguments.name("client")?;
+            let path = required_path(arguments, "path")?;
+            let (directory, file) = match path.rsplit_once('/') {
+                Some((directory, file)) if !file.is_empty() => (format!("{}/", directory), file),
+                _ => {
+                    return Err(InvalidParams {
+                        argument: "path".to_string(),
+                        reason: "must be the full path of a file".to_string(),
+                    }
+                    .into())
+                }
+            };
+
+            let fileset = arguments.optional_name("fileset")?;
+            let days = arguments
+                .optional_u32("days")?
+                .unwrap_or(HISTORY_DAYS)
+                .max(1);
+            let jobs = successful_backups(client, &client_name, fileset.as_deref(), days).await?;
+            let mut warnings = Vec::new();
+            if has_older_backups(client, &client_name, days).await? {
+                warnings.push(format!(
+                    "Only backups of the last {} days were searched; client '{}' has older ones, pass a larger days to include them",
+                    days, client_name
+                ));
+            }
+            if !jobs.is_empty() {
+                let job_ids: Vec<u64> = jobs.iter().map(|job| job.job_id).collect();
+                client.bvfs_update(&job_ids).await?;
+            }
+            let jobs: HashMap<u64, &Job> = jobs.iter().map(|job| (job.job_id, job)).collect();
+
+            let mut versions = client.bvfs_versions(&client_name, &directory, file).await?;
+            if fileset.is_some() {
+                versions.retain(|version| jobs.contains_key(&version.jobid));
+            }
+            versions.sort_by_key(|version| std::cmp::Reverse(version.jobid));
+            let versions: Vec<Value> = versions
+                .into_iter()
+                .map(|version| {
+                    let job = jobs.get(&version.jobid);
+                    json!({
+                        "jobid": version.jobid,
+                        "job": job.map(|job| job.name.clone()),
+                        "level": job.map(|job| job.level.code().to_string()),
+                        "backup_time": job.and_then(|job| job.start_time.clone()),
+                        "size": version.size,
+                        "mtime": version.mtime,
+                        "md5": version.md5,
+                        "volume": version.volume
+                    })
+                })
+                .map(|mut version| {
+                    // Leave out what the catalog did not know instead of sending nulls
+                    if let Value::Object(fields) = &mut version {
+                        fields.retain(|_, value| !value.is_null());
+                    }
+                    version
+                })
+                .collect();
+
+            Ok(ToolOutput::Structured(json!({
+                "client": client_name,
+                "path": path,
+                "versions": versions,
+                "history_days": days,
+                "warnings": warnings
+            })))
+        }
+        _ => Err(anyhow::anyhow!("Unknown tool: {}", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBconsole;
+
+    #[tokio::test]
+    async fn bounds_the_history_of_file_versions() {
+        let fake = FakeBconsole::new(
+            r#"
+  "llist jobs client=web01-fd jobtype=B days=30") echo '{"result":{"jobs":[
+    {"jobid":5,"name":"backup-web","clientname":"web01-fd","type":"B","level":"I","jobstatus":"T","jobtdate":"1700000000","starttime":"2023-11-14 22:13:20","fileset":"LinuxAll"},
+    {"jobid":6,"name":"backup-web","clientname":"web01-fd","type":"B","level":"I","jobstatus":"E","jobtdate":"1700086400","fileset":"LinuxAll"}]}}' ;;
+  "list jobs client=web01-fd jobtype=B count") echo '{"result":{"jobs":[{"count":"9"}]}}' ;;
+  "list jobs client=web01-fd jobtype=B days=30 count") echo '{"result":{"jobs":[{"count":"2"}]}}' ;;
+  ".bvfs_update jobid=5") echo '{"result":{}}' ;;
+  ".bvfs_versions client=web01-fd path=/etc/ fname=hosts") echo '{"result":{"versions":[
+    {"pathid":"2","fileid":"7","jobid":"5","lstat":"A A IH/ B A A A TS BAA C BlU/EA BlU/EA BlU/EA A A C","md5":"abcd","volumename":"Incr-0005"}]}}' ;;"#,
+        );
+        let client = fake.client();
+        let arguments = json!({ "client": "web01-fd", "path": "/etc/hosts", "days": 30 });
+        let ToolOutput::Structured(content) = call(
+            &client,
+            "list_file_versions",
+            &Arguments::new(&arguments).unwrap(),
+        )
+        .await
+        .unwrap() else {
+            panic!("list_file_versions returns structured content");
+        };
+
+        assert_eq!(content["history_days"], 30);
+        assert_eq!(content["versions"][0]["jobid"], 5);
+        assert_eq!(content["versions"][0]["level"], "I");
+        assert_eq!(
+            content["warnings"],
+            json!(["Only backups of the last 30 days were searched; client 'web01-fd' has older ones, pass a larger days to include them"])
+        );
+        // Only the successful backups of the window go into the BVFS cache
+        assert!(fake
+            .commands()
+            .contains(&".bvfs_update jobid=5".to_string()));
+    }
+
+    #[test]
+    fn counts_the_history_back_from_the_date() {
+        assert_eq!(history_days(None), HISTORY_DAYS);
+        let today = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            / 86400;
+        // 2024-03-01 is day 19783 of the epoch
+        assert_eq!(
+            history_days(Some("2024-03-01")),
+            (today - 19783) as u32 + HISTORY_DAYS
+        );
+        assert_eq!(history_days(Some("9999-12-31")), HISTORY_DAYS);
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use super::restore::volume_problem;
+use super::ToolOutput;
+use crate::bareos::{BareosClient, JobListParams};
+use crate::chain::{self, is_successful};
+use crate::model::{Job, JobType};
+use crate::validate::Arguments;
+
+pub fn definition() -> Value {
+    let mut link = Job::schema();
+    link["properties"]["volumes"] = json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "volumename": { "type": "string" },
+                "volstatus": { "type": "string" },
+                "problem": { "type": "string" }
+            },
+            "required": ["volumename"]
+        }
+    });
+    json!({
+        "name": "analyze_job_chain",
+        "description": "Reconstruct the backup chain a job depends on: the Full it builds on, the Differential and the Incrementals in between, picked from the catalog by client, FileSet, level and JobTDate like the Director does for a restore. Checks that every job in the chain still has usable volumes and reports the chain as broken when the base Full is missing (purged) or failed, or a volume was purged, recycled or lost. Answers 'what Full does this Incremental depend on and is it still restorable'.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "job_id": {
+                    "type": "string",
+                    "description": "The backup job ID to analyze"
+                }
+            },
+            "required": ["job_id"]
+        },
+        "outputSchema": {
+            "type": "object",
+            "properties": {
+                "job_id": { "type": "integer" },
+                "client": { "type": "string" },
+                "fileset": { "type": "string" },
+                "base_full": {
+                    "type": "integer",
+                    "description": "Job ID of the Full the chain builds on"
+                },
+                "chain": {
+                    "type": "array",
+                    "description": "Jobs a restore of job_id needs, oldest first, each with its volumes",
+                    "items": link
+                },
+                "failed_full": Job::schema(),
+                "broken": { "type": "boolean" },
+                "problems": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Why the chain is broken"
+                },
+                "warnings": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["job_id", "chain", "broken", "problems", "warnings"]
+        }
+    })
+}
+
+pub async fn call(client: &BareosClient, arguments: &Arguments<'_>) -> Result<ToolOutput> {
+    let job_id = arguments.job_id("job_id")?;
+    let job = client
+        .get_job_record(job_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Job {} not found in the catalog", job_id))?;
+    if job.job_type != JobType::Backup {
+        anyhow::bail!(
+            "Job {} is not a backup (type {}); only backups form chains",
+            job_id,
+            job.job_type.code()
+        );
+    }
+    let Some(client_name) = job.client.clone() else {
+        anyhow::bail!("Job {} has no client in the catalog", job_id);
+    };
+
+    let history = client
+        .list_jobs(JobListParams {
+            client: Some(client_name.clone()),
+            jobtype: Some("B".to_string()),
+            long: true,
+            ..Default::default()
+        })
+        .await?;
+    let chain = chain::resolve(&job, &history);
+
+    let mut problems = Vec::new();
+    let mut warnings = Vec::new();
+    match (chain.full, chain.failed_full) {
+        (None, Some(failed)) => problems.push(format!(
+            "No successful Full backup precedes job {}; the last Full, job {}, ended with status {}",
+            job_id,
+            failed.job_id,
+            failed.status.code()
+        )),
+        (None, None) => problems.push(format!(
+            "No Full backup of client '{}' with this FileSet precedes job {} in the catalog; it was purged or never ran",
+            client_name, job_id
+        )),
+        (Some(full), Some(failed)) => warnings.push(format!(
+            "The last Full attempt, job {}, ended with status {}; the chain builds on the older Full job {}",
+            failed.job_id,
+            failed.status.code(),
+            full.job_id
+        )),
+        (Some(_), None) => {}
+    }
+    if !is_successful(&job) {
+        warnings.push(format!(
+            "Job {} itself ended with status {}",
+            job_id,
+            job.status.code()
+        ));
+    }
+
+    let volumes = client.list_volumes(None).await?;
+    let mut jobs = Vec::new();
+    for link in chain.jobs() {
+        let mut entry = serde_json::to_value(link)?;
+        let mut link_volumes = Vec::new();
+        let media = client.list_job_media(link.job_id).await?;
+        if media.is_empty() {
+            problems.push(format!(
+                "Job {} has no volumes in the catalog; they were purged or recycled",
+                link.job_id
+            ));
+        }
+        for record in media {
+            if link_volumes
+                .iter()
+                .any(|volume: &Value| volume["volumename"] == record.volume)
+            {
+                continue;
+            }
+            let mut volume = json!({ "volumename": record.volume });
+            let problem = match volumes.iter().find(|volume| volume.name == record.volume) {
+                Some(catalog) => {
+                    volume["volstatus"] = json!(catalog.status);
+                    volume_problem(catalog)
+                }
+                None => Some("is not in the catalog".to_string()),
+            };
+            if let Some(problem) = problem {
+                problems.push(format!(
+                    "Volume {} of job {} {}",
+                    record.volume, link.job_id, problem
+                ));
+                volume["problem"] = json!(problem);
+            }
+            link_volumes.push(volume);
+        }
+        if link.purged_files == Some(true) {
+            warnings.push(format!(
+                "The file records of job {} were pruned; it can only be restored as a whole",
+                link.job_id
+            ));
+        }
+        entry["volumes"] = json!(link_volumes);
+        jobs.push(entry);
+    }
+
+    let mut result = json!({
+        "job_id": job_id,
+        "client": client_name,
+        "chain": jobs,
+        "broken": !problems.is_empty(),
+        "problems": problems,
+        "warnings": warnings
+    });
+    if let Some(fileset) = &job.fileset {
+        result["fileset"] = json!(fileset);
+    }
+    if let Some(full) = chain.full {
+        result["base_full"] = json!(full.job_id);
+    }
+    if let Some(failed) = chain.failed_full {
+        result["failed_full"] = serde_json::to_value(failed)?;
+    }
+    Ok(ToolOutput::Structured(result))
+}
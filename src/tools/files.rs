@@ -0,0 +1,553 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt::Write;
+
+use super::ToolOutput;
+use crate::auth::glob_match;
+use crate::bareos::BareosClient;
+use crate::model::FileEntry;
+use crate::validate::{self, Arguments, InvalidParams};
+
+const DEFAULT_LIMIT: u32 = 1000;
+const MAX_LIMIT: u32 = 10_000;
+
+// Keeps a page well inside a model's context even when the limit allows long paths
+const MAX_PAGE_BYTES: usize = 64 * 1024;
+
+// Quotes, braces and the key of each `{"filename": ...}` record
+const RECORD_OVERHEAD: usize = 16;
+
+// BVFS commands sent for one page, so a filter that matches little does not walk the whole
+// tree in one call
+const MAX_COMMANDS_PER_PAGE: usize = 200;
+
+pub fn definition() -> Value {
+    json!({
+        "name": "list_files",
+        "description": "List the files backed up in a specific job, one page at a time, walking its directory tree depth first. Narrow the listing with path_prefix or glob, and page through it with cursor: as long as has_more is true, call again with cursor set to next_cursor. Pages are cut short at 64 KiB of file names (truncated=true), and a page may hold fewer files than the limit when the filters skip many. For the versions of a single file across jobs use the backup browser tools instead.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "job_id": {
+                    "type": "string",
+                    "description": "The job ID to list files for"
+                },
+                "path_prefix": {
+                    "type": "string",
+                    "description": "Only list files whose path starts with this, e.g. /etc/nginx/; the walk starts in its directory"
+                },
+                "glob": {
+                    "type": "string",
+                    "description": "Only list files whose full path matches this pattern; * matches any characters including /, ? matches one, e.g. */nginx.conf"
+                },
+                "cursor": {
+                    "type": "string",
+                    "description": "Where to continue; use next_cursor from the previous page with the same job_id and filters"
+                },
+                "limit": {
+                    "type": "number",
+                    "description": "Maximum number of files to return (default 1000, at most 10000)"
+                }
+            },
+            "required": ["job_id"]
+        },
+        "outputSchema": {
+            "type": "object",
+            "properties": {
+                "job_id": { "type": "integer" },
+                "total": {
+                    "type": "integer",
+                    "description": "Number of files in the job, as its catalog record counts them; only given without filters"
+                },
+                "returned": { "type": "integer" },
+                "filenames": {
+                    "type": "array",
+                    "items": FileEntry::schema()
+                },
+                "has_more": { "type": "boolean" },
+                "next_cursor": { "type": "string" },
+                "truncated": {
+                    "type": "boolean",
+                    "description": "Whether the page was cut short by the size budget rather than the limit"
+                },
+                "skipped_directories": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Directories left out because their names cannot be passed to the Director"
+                },
+                "message": { "type": "string" }
+            },
+            "required": ["job_id", "returned", "filenames", "has_more", "truncated"]
+        }
+    })
+}
+
+/// A directory on the way from where the walk started to where it stands.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Frame {
+    path: String,
+    /// Files of `path` listed so far, or `None` once all of them are.
+    files: Option<usize>,
+    /// Director offset of the next subdirectory of `path` to descend into.
+    dirs: usize,
+}
+
+impl Frame {
+    fn new(path: String) -> Self {
+        Self {
+            path,
+            files: Some(0),
+            dirs: 0,
+        }
+    }
+}
+
+/// Where the next page starts, with the job and filters of the listing it continues.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Cursor {
+    job_id: u64,
+    path_prefix: Option<String>,
+    glob: Option<String>,
+    stack: Vec<Frame>,
+}
+
+struct Filter<'a> {
+    path_prefix: Option<&'a str>,
+    glob: Option<&'a str>,
+}
+
+impl Filter<'_> {
+    fn matches(&self, filename: &str) -> bool {
+        self.path_prefix
+            .is_none_or(|prefix| filename.starts_with(prefix))
+            && self.glob.is_none_or(|glob| glob_match(glob, filename))
+    }
+
+    // Whether files below `directory` can match the prefix
+    fn enters(&self, directory: &str) -> bool {
+        self.path_prefix
+            .is_none_or(|prefix| directory.starts_with(prefix) || prefix.starts_with(directory))
+    }
+}
+
+// The cursor is hex-encoded JSON so callers pass it back unchanged
+fn encode_cursor(cursor: &Cursor) -> String {
+    serde_json::to_vec(cursor)
+        .unwrap_or_default()
+        .iter()
+        .fold(String::new(), |mut cursor, byte| {
+            let _ = write!(cursor, "{:02x}", byte);
+            cursor
+        })
+}
+
+fn decode_cursor(cursor: &str) -> Result<Cursor, InvalidParams> {
+    let invalid = || InvalidParams {
+        argument: "cursor".to_string(),
+        reason: "is not a next_cursor returned by list_files".to_string(),
+    };
+    if !cursor.len().is_multiple_of(2) || !cursor.is_ascii() {
+        return Err(invalid());
+    }
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| invalid())?;
+    let cursor: Cursor = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+    // The paths go into BVFS commands, so they pass the same checks as path arguments
+    if cursor.stack.is_empty()
+        || cursor
+            .stack
+            .iter()
+            .any(|frame| validate::path("cursor", &frame.path).is_err())
+    {
+        return Err(invalid());
+    }
+    Ok(cursor)
+}
+
+// BVFS paths are directories ending in a slash; a prefix starts the walk in its directory
+fn start_directory(path_prefix: Option<&str>) -> String {
+    match path_prefix.and_then(|prefix| prefix.rsplit_once('/')) {
+        Some((directory, _)) => format!("{}/", directory),
+        None => "/".to_string(),
+    }
+}
+
+struct Page {
+    files: Vec<FileEntry>,
+    truncated: bool,
+    skipped: Vec<String>,
+}
+
+// Walks the job's tree from `stack` until the page holds `limit` files or `MAX_PAGE_BYTES`,
+// asking the Director for at most the files still missing; `stack` is left where the next
+// page starts and is empty once the walk is done
+async fn walk(
+    client: &BareosClient,
+    job_id: u64,
+    filter: &Filter<'_>,
+    stack: &mut Vec<Frame>,
+    limit: usize,
+) -> Result<Page> {
+    let mut page = Page {
+        files: Vec::new(),
+        truncated: false,
+        skipped: Vec::new(),
+    };
+    let mut bytes = 0;
+    let mut commands = 0;
+
+    while let Some(frame) = stack.last_mut() {
+        if page.files.len() == limit || commands == MAX_COMMANDS_PER_PAGE {
+            break;
+        }
+        commands += 1;
+
+        if let Some(listed) = frame.files {
+            let wanted = limit - page.files.len();
+            let entries = client
+                .bvfs_lsfiles(&[job_id], &frame.path, listed, wanted)
+                .await?;
+            frame.files = (entries.len() == wanted).then_some(listed + wanted);
+            for (i, entry) in entries.iter().enumerate() {
+                let filename = format!("{}{}", frame.path, entry.name);
+                if !filter.matches(&filename) {
+                    continue;
+                }
+                let size = filename.len() + RECORD_OVERHEAD;
+                // Always return at least one file, or a huge name would stall the paging
+                if !page.files.is_empty() && bytes + size > MAX_PAGE_BYTES {
+                    frame.files = Some(listed + i);
+                    page.truncated = true;
+                    return Ok(page);
+                }
+                bytes += size;
+                page.files.push(FileEntry { filename });
+            }
+            continue;
+        }
+
+        let entries = client
+            .bvfs_lsdirs(&[job_id], &frame.path, frame.dirs, 1)
+            .await?;
+        let Some(entry) = entries.into_iter().next() else {
+            stack.pop();
+            continue;
+        };
+        frame.dirs += 1;
+        if entry.name == "." || entry.name == ".." {
+            continue;
+        }
+        let mut directory = format!("{}{}", frame.path, entry.name);
+        if !directory.ends_with('/') {
+            directory.push('/');
+        }
+        if !filter.enters(&directory) {
+            continue;
+        }
+        match validate::path("path", &directory) {
+            Ok(directory) => stack.push(Frame::new(directory)),
+            Err(_) => page.skipped.push(directory),
+        }
+    }
+    Ok(page)
+}
+
+pub async fn call(client: &BareosClient, arguments: &Arguments<'_>) -> Result<ToolOutput> {
+    let job_id = arguments.job_id("job_id")?;
+    let path_prefix = arguments.optional_path("path_prefix")?;
+    let glob = arguments.optional_path("glob")?;
+    let limit = match arguments.optional_u32("limit")? {
+        Some(0) => {
+            return Err(InvalidParams {
+                argument: "limit".to_string(),
+                reason: "must be at least 1".to_string(),
+            }
+            .into())
+        }
+        Some(limit) => limit.min(MAX_LIMIT),
+        None => DEFAULT_LIMIT,
+    } as usize;
+    let mut stack = match arguments.optional_str("cursor")? {
+        Some(cursor) => {
+            let cursor = decode_cursor(cursor)?;
+            if cursor.job_id != job_id || cursor.path_prefix != path_prefix || cursor.glob != glob {
+                return Err(InvalidParams {
+                    argument: "cursor".to_string(),
+                    reason: "belongs to another job_id, path_prefix or glob; pass the ones of the first page".to_string(),
+                }
+                .into());
+            }
+            cursor.stack
+        }
+        None => {
+            // The Director pages through its BVFS cache, so a page never reads the whole
+            // job; the first page fills the cache for the rest
+            client.bvfs_update(&[job_id]).await?;
+            vec![Frame::new(start_directory(path_prefix.as_deref()))]
+        }
+    };
+
+    let filter = Filter {
+        path_prefix: path_prefix.as_deref(),
+        glob: glob.as_deref(),
+    };
+    let page = walk(client, job_id, &filter, &mut stack, limit).await?;
+
+    let has_more = !stack.is_empty();
+    let mut content = json!({
+        "job_id": job_id,
+        "returned": page.files.len(),
+        "filenames": page.files,
+        "has_more": has_more,
+        "truncated": page.truncated
+    });
+    if path_prefix.is_none() && glob.is_none() {
+        let jobs = client.get_job_status(job_id).await?;
+        if let Some(total) = jobs.first().and_then(|job| job.files) {
+            content["total"] = json!(total);
+        }
+    }
+    if !page.skipped.is_empty() {
+        content["skipped_directories"] = json!(page.skipped);
+    }
+    if has_more {
+        let reason = if page.truncated {
+            format!("Truncated at the {} KiB page budget", MAX_PAGE_BYTES / 1024)
+        } else if page.files.len() < limit {
+            "Stopped before the whole tree was searched".to_string()
+        } else {
+            format!("Showing {} files", page.files.len())
+        };
+        let cursor = encode_cursor(&Cursor {
+            job_id,
+            path_prefix: path_prefix.clone(),
+            glob: glob.clone(),
+            stack,
+        });
+        content["message"] = json!(format!(
+            "{}; call list_files again with cursor=\"{}\" for the next page",
+            reason, cursor
+        ));
+        content["next_cursor"] = json!(cursor);
+    }
+    Ok(ToolOutput::Structured(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBconsole;
+
+    // /etc/hosts, /etc/nginx/{nginx.conf,sites/{default,shop.conf}} and /srv/www/index.html,
+    // as the BVFS cache of job 5 lists them
+    const TREE: &str = r#"
+  ".bvfs_lsdirs jobid=5 path=/ offset=0 limit=1") printf '1\t0\t5\tA\t.\n' ;;
+  ".bvfs_lsdirs jobid=5 path=/ offset=1 limit=1") printf '2\t0\t5\tA\tetc/\n' ;;
+  ".bvfs_lsdirs jobid=5 path=/ offset=2 limit=1") printf '5\t0\t5\tA\tsrv/\n' ;;
+  ".bvfs_lsdirs jobid=5 path=/etc/ offset=0 limit=1") printf '3\t0\t5\tA\tnginx/\n' ;;
+  ".bvfs_lsdirs jobid=5 path=/etc/nginx/ offset=0 limit=1") printf '4\t0\t5\tA\tsites/\n' ;;
+  ".bvfs_lsdirs jobid=5 path=/srv/ offset=0 limit=1") printf '6\t0\t5\tA\twww/\n' ;;
+  ".bvfs_lsfiles jobid=5 path=/etc/ offset=0 "*) printf '2\t10\t5\tA\thosts\n' ;;
+  ".bvfs_lsfiles jobid=5 path=/etc/nginx/ offset=0 "*) printf '3\t11\t5\tA\tnginx.conf\n' ;;
+  ".bvfs_lsfiles jobid=5 path=/etc/nginx/sites/ offset=0 limit=1") printf '4\t12\t5\tA\tdefault\n' ;;
+  ".bvfs_lsfiles jobid=5 path=/etc/nginx/sites/ offset=0 "*) printf '4\t12\t5\tA\tdefault\n4\t13\t5\tA\tshop.conf\n' ;;
+  ".bvfs_lsfiles jobid=5 path=/etc/nginx/sites/ offset=1 "*) printf '4\t13\t5\tA\tshop.conf\n' ;;
+  ".bvfs_lsfiles jobid=5 path=/srv/www/ offset=0 "*) printf '6\t14\t5\tA\tindex.html\n' ;;
+  "list jobid=5") echo '{"result":{"jobs":[{"jobid":5,"name":"backup-web","type":"B","level":"F","jobstatus":"T","jobfiles":5}]}}' ;;"#;
+
+    async fn list(fake: &FakeBconsole, arguments: Value) -> Value {
+        let ToolOutput::Structured(content) =
+            call(&fake.client(), &Arguments::new(&arguments).unwrap())
+                .await
+                .unwrap()
+        else {
+            panic!("expected a structured result");
+        };
+        content
+    }
+
+    fn names(page: &Value) -> Vec<&str> {
+        page["filenames"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| file["filename"].as_str().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn pages_through_the_tree_on_the_director() {
+        let fake = FakeBconsole::new(TREE);
+        let first = list(&fake, json!({ "job_id": 5, "limit": 3 })).await;
+        assert_eq!(
+            names(&first),
+            vec![
+                "/etc/hosts",
+                "/etc/nginx/nginx.conf",
+                "/etc/nginx/sites/default"
+            ]
+        );
+        assert_eq!(first["total"], 5);
+        assert_eq!(first["has_more"], true);
+        assert_eq!(first["truncated"], false);
+        let cursor = first["next_cursor"].as_str().unwrap();
+        assert!(first["message"].as_str().unwrap().contains(cursor));
+        // Only the files still missing from the page are asked for
+        assert!(fake.commands().contains(
+            &".bvfs_lsfiles jobid=5 path=/etc/nginx/sites/ offset=0 limit=1".to_string()
+        ));
+
+        assert_eq!(
+            fake.commands()
+                .iter()
+                .filter(|command| command.starts_with(".bvfs_update"))
+                .count(),
+            1
+        );
+
+        let last = list(&fake, json!({ "job_id": 5, "limit": 3, "cursor": cursor })).await;
+        assert_eq!(
+            names(&last),
+            vec!["/etc/nginx/sites/shop.conf", "/srv/www/index.html"]
+        );
+        assert_eq!(last["has_more"], false);
+        assert!(last.get("next_cursor").is_none());
+        assert!(last.get("message").is_none());
+        assert!(!fake
+            .commands()
+            .iter()
+            .any(|command| command.starts_with("list files")));
+        // The cache was filled for the first page
+        assert_eq!(
+            fake.commands()
+                .iter()
+                .filter(|command| command.starts_with(".bvfs_update"))
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn filters_by_path_prefix_and_glob() {
+        let fake = FakeBconsole::new(TREE);
+        let nginx = list(&fake, json!({ "job_id": 5, "path_prefix": "/etc/nginx/" })).await;
+        assert_eq!(
+            names(&nginx),
+            vec![
+                "/etc/nginx/nginx.conf",
+                "/etc/nginx/sites/default",
+                "/etc/nginx/sites/shop.conf"
+            ]
+        );
+        assert!(nginx.get("total").is_none());
+        assert!(!fake
+            .commands()
+            .iter()
+            .any(|command| command.contains("path=/srv/")));
+
+        let conf = list(
+            &fake,
+            json!({ "job_id": 5, "path_prefix": "/etc/", "glob": "*.conf" }),
+        )
+        .await;
+        assert_eq!(
+            names(&conf),
+            vec!["/etc/nginx/nginx.conf", "/etc/nginx/sites/shop.conf"]
+        );
+        let partial = list(&fake, json!({ "job_id": 5, "path_prefix": "/etc/ng" })).await;
+        assert_eq!(partial["returned"], 3);
+    }
+
+    #[tokio::test]
+    async fn refuses_forged_cursors() {
+        let fake = FakeBconsole::new(TREE);
+        let cursor = |job_id, stack| {
+            encode_cursor(&Cursor {
+                job_id,
+                path_prefix: None,
+                glob: None,
+                stack,
+            })
+        };
+        let forged = cursor(5, vec![Frame::new("/etc\" jobid=1 \"/".to_string())]);
+        let other_job = cursor(6, vec![Frame::new("/etc/".to_string())]);
+        for cursor in [forged.as_str(), other_job.as_str(), "zz", "", "7b7d"] {
+            let arguments = json!({ "job_id": 5, "cursor": cursor });
+            let Err(error) = call(&fake.client(), &Arguments::new(&arguments).unwrap()).await
+            else {
+                panic!("accepted cursor {:?}", cursor);
+            };
+            assert!(
+                error.downcast_ref::<InvalidParams>().is_some(),
+                "{}",
+                cursor
+            );
+        }
+        let stack = vec![Frame::new("/etc/".to_string())];
+        assert_eq!(
+            decode_cursor(&cursor(5, stack.clone())).unwrap().stack,
+            stack
+        );
+
+        // A cursor only continues the listing with the filters it was made for
+        let first = list(&fake, json!({ "job_id": 5, "glob": "*.conf", "limit": 1 })).await;
+        let cursor = first["next_cursor"].as_str().unwrap();
+        for arguments in [
+            json!({ "job_id": 5, "cursor": cursor }),
+            json!({ "job_id": 5, "glob": "*", "cursor": cursor }),
+            json!({ "job_id": 5, "glob": "*.conf", "path_prefix": "/etc/", "cursor": cursor }),
+        ] {
+            let Err(error) = call(&fake.client(), &Arguments::new(&arguments).unwrap()).await
+            else {
+                panic!("accepted {}", arguments);
+            };
+            assert!(error.downcast_ref::<InvalidParams>().is_some());
+        }
+        let next = list(
+            &fake,
+            json!({ "job_id": 5, "glob": "*.conf", "limit": 1, "cursor": cursor }),
+        )
+        .await;
+        assert_eq!(names(&next), vec!["/etc/nginx/sites/shop.conf"]);
+    }
+
+    #[tokio::test]
+    async fn cuts_pages_at_the_size_budget() {
+        let long = "x".repeat(1000);
+        let fake = FakeBconsole::new(&format!(
+            r#"
+  ".bvfs_lsfiles jobid=5 path=/data/ offset=0 "*) for i in $(seq 100); do printf '1\t%s\t5\tA\t{long}\n' "$i"; done ;;"#
+        ));
+        let first = list(
+            &fake,
+            json!({ "job_id": 5, "path_prefix": "/data/", "limit": 100 }),
+        )
+        .await;
+        let per_file = "/data/".len() + long.len() + RECORD_OVERHEAD;
+        let returned = MAX_PAGE_BYTES / per_file;
+        assert_eq!(first["returned"], returned);
+        assert_eq!(first["truncated"], true);
+        assert_eq!(first["has_more"], true);
+        assert!(first["message"].as_str().unwrap().contains("64 KiB"));
+        let cursor = decode_cursor(first["next_cursor"].as_str().unwrap()).unwrap();
+        assert_eq!(cursor.stack[0].files, Some(returned));
+
+        // A name larger than the whole budget still makes progress
+        let huge = "y".repeat(MAX_PAGE_BYTES * 2);
+        let fake = FakeBconsole::new(&format!(
+            r#"
+  ".bvfs_lsfiles jobid=5 path=/data/ offset=0 "*) printf '1\t1\t5\tA\t{huge}\n1\t2\t5\tA\thosts\n' ;;"#
+        ));
+        let first = list(
+            &fake,
+            json!({ "job_id": 5, "path_prefix": "/data/", "limit": 10 }),
+        )
+        .await;
+        assert_eq!(first["returned"], 1);
+        assert_eq!(first["has_more"], true);
+    }
+}
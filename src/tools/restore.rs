@@ -0,0 +1,435 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::browse::{backup_as_of, history_days, restore_chain, successful_backups};
+use super::ToolOutput;
+use crate::bareos::BareosClient;
+use crate::model::{Job, Volume};
+use crate::validate::Arguments;
+
+pub fn definitions() -> Vec<Value> {
+    vec![json!({
+        "name": "plan_restore",
+        "description": "Dry run of a restore: works out which jobs, volumes and storages restoring paths of a client as of a date needs, like bconsole's 'most recent backup before date'. Returns the Full/Differential/Incremental chain, the volumes with their status and location, whether each path is in the backup, an estimate of the files and bytes to restore, and warnings about purged, recycled or missing volumes. Starts nothing.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "client": {
+                    "type": "string",
+                    "description": "The client whose files to restore"
+                },
+                "paths": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Files or directories (ending in '/') to restore, e.g. [\"/etc/nginx/\"]"
+                },
+                "date": {
+                    "type": "string",
+                    "description": "Restore the state as of the end of this day (YYYY-MM-DD); defaults to the latest backup"
+                },
+                "fileset": {
+                    "type": "string",
+                    "description": "Only use backups of this FileSet; defaults to the FileSet of the latest backup"
+                }
+            },
+            "required": ["client", "paths"]
+        },
+        "outputSchema": {
+            "type": "object",
+            "properties": {
+                "client": { "type": "string" },
+                "fileset": { "type": "string" },
+                "jobs": { "type": "array", "items": Job::schema() },
+                "paths": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "path": { "type": "string" },
+                            "found": { "type": "boolean" },
+                            "jobid": { "type": "integer" },
+                            "size": { "type": "integer" }
+                        },
+                        "required": ["path", "found"]
+                    }
+                },
+                "volumes": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "volumename": { "type": "string" },
+                            "jobids": { "type": "array", "items": { "type": "integer" } },
+                            "volstatus": { "type": "string" },
+                            "pool": { "type": "string" },
+                            "storage": { "type": "string" },
+                            "mediatype": { "type": "string" },
+                            "inchanger": { "type": "boolean" },
+                            "slot": { "type": "integer" },
+                            "problem": { "type": "string" }
+                        },
+                        "required": ["volumename", "jobids"]
+                    }
+                },
+                "storages": { "type": "array", "items": { "type": "string" } },
+                "estimated_files": { "type": "integer" },
+                "estimated_bytes": { "type": "integer" },
+                "estimate": {
+                    "type": "string",
+                    "description": "'paths' when the estimate sums the requested files, 'jobs' when it is the size of the whole chain (an upper bound)"
+                },
+                "warnings": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["client", "jobs", "paths", "volumes", "storages", "estimated_files", "estimated_bytes", "estimate", "warnings"]
+        }
+    })]
+}
+
+// Volume states whose data a restore cannot rely on
+pub(super) fn volume_problem(volume: &Volume) -> Option<String> {
+    let problem = match volume.status.as_str() {
+        "Purged" => "is purged: its jobs are no longer in the catalog",
+        "Recycle" => "is marked for recycling and may be overwritten",
+        "Error" => "is in error state",
+        "Disabled" => "is disabled",
+        _ if volume.enabled == Some(false) => "is disabled",
+        _ => return None,
+    };
+    Some(problem.to_string())
+}
+
+struct PathCheck {
+    path: String,
+    found: bool,
+    job_id: Option<u64>,
+    size: Option<u64>,
+}
+
+async fn check_path(
+    client: &BareosClient,
+    client_name: &str,
+    job_ids: &[u64],
+    path: &str,
+) -> Result<PathCheck> {
+    let mut check = PathCheck {
+        path: path.to_string(),
+        found: false,
+        job_id: None,
+        size: None,
+    };
+    match path.rsplit_once('/') {
+        // A directory: found if it has any entry in the chain
+        Some((_, "")) => {
+            let files = client.bvfs_lsfiles(job_ids, path, 0, 1).await?;
+            let directories = client.bvfs_lsdirs(job_ids, path, 0, 3).await?;
+            check.found = !files.is_empty()
+                || directories
+                    .iter()
+                    .any(|entry| entry.name != "." && entry.name != "..");
+        }
+        Some((directory, file)) => {
+            let version = client
+                .bvfs_versions(client_name, &format!("{}/", directory), file)
+                .await?
+                .into_iter()
+                .filter(|version| job_ids.contains(&version.jobid))
+                .max_by_key(|version| version.jobid);
+            if let Some(version) = version {
+                check.found = true;
+                check.job_id = Some(version.jobid);
+                check.size = version.size;
+            }
+        }
+        None => {}
+    }
+    Ok(check)
+}
+
+pub async fn call(
+    client: &BareosClient,
+    name: &str,
+    arguments: &Arguments<'_>,
+) -> Result<ToolOutput> {
+    match name {
+        "plan_restore" => {
+            let client_name = arguments.name("client")?;
+            let paths = arguments.paths("paths")?;
+            let date = arguments.optional_date("date")?;
+            let fileset = arguments.optional_name("fileset")?;
+
+            let (job, mut warnings) =
+                backup_as_of(client, &client_name, fileset.as_deref(), date.as_deref()).await?;
+            let (job_ids, warning) = restore_chain(client, &job).await?;
+            warnings.extend(warning);
+
+            let backups = successful_backups(
+                client,
+                &client_name,
+                job.fileset.as_deref(),
+                history_days(date.as_deref()),
+            )
+            .await?;
+            let mut jobs = Vec::new();
+            for job_id in &job_ids {
+                match backups.iter().find(|job| job.job_id == *job_id) {
+                    Some(job) => jobs.push(job.clone()),
+                    None => jobs.extend(client.get_job_status(*job_id).await?),
+                }
+            }
+
+            let mut checks = Vec::new();
+            for path in &paths {
+                let check = check_path(client, &client_name, &job_ids, path).await?;
+                if !check.found {
+                    warnings.push(format!("{} is not in the backups", check.path));
+                }
+                checks.push(check);
+            }
+
+            // Volume name -> jobs on it, in volume name order
+            let mut volume_jobs: BTreeMap<String, BTreeSet<u64>> = BTreeMap::new();
+            for job_id in &job_ids {
+                let media = client.list_job_media(*job_id).await?;
+                if media.is_empty() {
+                    warnings.push(format!("Job {} has no volumes in the catalog", job_id));
+                }
+                for record in media {
+                    volume_jobs
+                        .entry(record.volume)
+                        .or_default()
+                        .insert(record.job_id);
+                }
+            }
+
+            let catalog = client.list_volumes(None).await?;
+            let mut storages = BTreeSet::new();
+            let mut volumes = Vec::new();
+            for (name, jobids) in volume_jobs {
+                let mut entry = json!({ "volumename": name, "jobids": jobids });
+                match catalog.iter().find(|volume| volume.name == name) {
+                    Some(volume) => {
+                        for (key, value) in [
+                            ("volstatus", json!(volume.status)),
+                            ("pool", json!(volume.pool)),
+                            ("storage", json!(volume.storage)),
+                            ("mediatype", json!(volume.media_type)),
+                            ("inchanger", json!(volume.in_changer)),
+                            ("slot", json!(volume.slot)),
+                        ] {
+                            if !value.is_null() {
+                                entry[key] = value;
+                            }
+                        }
+                        storages.extend(volume.storage.clone());
+                        if let Some(problem) = volume_problem(volume) {
+                            warnings.push(format!("Volume {} {}", name, problem));
+                            entry["problem"] = json!(problem);
+                        }
+                    }
+                    None => {
+                        let problem = "is not in the catalog";
+                        warnings.push(format!("Volume {} {}", name, problem));
+                        entry["problem"] = json!(problem);
+                    }
+                }
+                volumes.push(entry);
+            }
+
+            // Exact for files; a directory may hold anything up to the whole chain
+            let (estimate, estimated_files, estimated_bytes) =
+                if checks.iter().all(|check| check.size.is_some()) {
+                    (
+                        "paths",
+                        checks.len() as u64,
+                        checks.iter().filter_map(|check| check.size).sum(),
+                    )
+                } else {
+                    (
+                        "jobs",
+                        jobs.iter().filter_map(|job| job.files).sum(),
+                        jobs.iter().filter_map(|job| job.bytes).sum::<u64>(),
+                    )
+                };
+
+            let paths: Vec<Value> = checks
+                .into_iter()
+                .map(|check| {
+                    let mut path = json!({ "path": check.path, "found": check.found });
+                    if let Some(job_id) = check.job_id {
+                        path["jobid"] = json!(job_id);
+                    }
+                    if let Some(size) = check.size {
+                        path["size"] = json!(size);
+                    }
+                    path
+                })
+                .collect();
+
+            let mut content = json!({
+                "client": client_name,
+                "jobs": jobs,
+                "paths": paths,
+                "volumes": volumes,
+                "storages": storages,
+                "estimated_files": estimated_files,
+                "estimated_bytes": estimated_bytes,
+                "estimate": estimate,
+                "warnings": warnings
+            });
+            if let Some(fileset) = &job.fileset {
+                content["fileset"] = json!(fileset);
+            }
+            Ok(ToolOutput::Structured(content))
+        }
+        _ => Err(anyhow::anyhow!("Unknown tool: {}", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBconsole;
+
+    // Client web01-fd: a LinuxAll Full (job 1, volume Full-0001) and Incremental (job 2, on
+    // the purged Incr-0002), and a later Full of the Catalog FileSet (job 3) without volumes
+    const CATALOG: &str = r#"
+  "llist jobs client=web01-fd jobtype=B days="*) echo '{"result":{"jobs":[
+    {"jobid":1,"name":"backup-web","clientname":"web01-fd","type":"B","level":"F","jobstatus":"T","starttime":"2024-03-01 01:00:00","jobfiles":10,"jobbytes":1000,"fileset":"LinuxAll"},
+    {"jobid":2,"name":"backup-web","clientname":"web01-fd","type":"B","level":"I","jobstatus":"T","starttime":"2024-03-02 01:00:00","jobfiles":2,"jobbytes":100,"fileset":"LinuxAll"},
+    {"jobid":4,"name":"backup-web","clientname":"web01-fd","type":"B","level":"I","jobstatus":"f","starttime":"2024-03-02 12:00:00","fileset":"LinuxAll"},
+    {"jobid":3,"name":"backup-catalog","clientname":"web01-fd","type":"B","level":"F","jobstatus":"T","starttime":"2024-03-03 01:00:00","jobfiles":1,"jobbytes":5000,"fileset":"Catalog"}]}}' ;;
+  ".bvfs_get_jobids jobid=2") echo '{"result":{"jobids":[{"id":"1"},{"id":"2"}]}}' ;;
+  ".bvfs_get_jobids jobid=3") echo '{"result":{"jobids":[]}}' ;;
+  ".bvfs_versions client=web01-fd path=/etc/ fname=hosts") echo '{"result":{"versions":[
+    {"pathid":"2","fileid":"7","jobid":"1","lstat":"A A IH/ B A A A TS BAA C BlU/EA BlU/EA BlU/EA A A C","md5":"abcd","volumename":"Full-0001"}]}}' ;;
+  ".bvfs_lsfiles jobid=1,2 path=/etc/nginx/ offset=0 limit=1") echo '{"result":{"files":[
+    {"type":"F","pathid":"3","fileid":"8","jobid":"2","lstat":"A A IH/ B A A A TS BAA C BlU/EA BlU/EA BlU/EA A A C","name":"nginx.conf"}]}}' ;;
+  "list jobmedia jobid=1") echo '{"result":{"jobmedia":[{"jobid":"1","volumename":"Full-0001"}]}}' ;;
+  "list jobmedia jobid=2") echo '{"result":{"jobmedia":[{"jobid":"2","volumename":"Incr-0002"}]}}' ;;
+  "list volumes") echo '{"result":{"volumes":{
+    "Full":[{"mediaid":1,"volumename":"Full-0001","volstatus":"Full","storage":"File","inchanger":0}],
+    "Incremental":[{"mediaid":2,"volumename":"Incr-0002","volstatus":"Purged","storage":"File"}]}}}' ;;"#;
+
+    async fn plan(fake: &FakeBconsole, arguments: Value) -> Value {
+        let client = fake.client();
+        match call(
+            &client,
+            "plan_restore",
+            &Arguments::new(&arguments).unwrap(),
+        )
+        .await
+        .unwrap()
+        {
+            ToolOutput::Structured(content) => content,
+            _ => panic!("plan_restore returns structured content"),
+        }
+    }
+
+    fn volume(status: &str, enabled: Option<bool>) -> Volume {
+        serde_json::from_value(json!({
+            "mediaid": 1,
+            "volumename": "Full-0001",
+            "volstatus": status,
+            "enabled": enabled
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn flags_volumes_a_restore_cannot_rely_on() {
+        for status in ["Purged", "Recycle", "Error", "Disabled"] {
+            assert!(
+                volume_problem(&volume(status, None)).is_some(),
+                "{}",
+                status
+            );
+        }
+        for status in ["Full", "Append", "Used"] {
+            assert!(
+                volume_problem(&volume(status, Some(true))).is_none(),
+                "{}",
+                status
+            );
+        }
+        assert_eq!(
+            volume_problem(&volume("Full", Some(false))).as_deref(),
+            Some("is disabled")
+        );
+    }
+
+    #[tokio::test]
+    async fn plans_a_restore_from_the_chain_of_one_fileset() {
+        let fake = FakeBconsole::new(CATALOG);
+        let plan = plan(
+            &fake,
+            json!({
+                "client": "web01-fd",
+                "paths": ["/etc/hosts", "/etc/nginx/"],
+                "fileset": "LinuxAll"
+            }),
+        )
+        .await;
+
+        assert_eq!(plan["fileset"], "LinuxAll");
+        let jobs: Vec<u64> = plan["jobs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|job| job["jobid"].as_u64().unwrap())
+            .collect();
+        assert_eq!(jobs, vec![1, 2]);
+        assert_eq!(
+            plan["paths"][0],
+            json!({ "path": "/etc/hosts", "found": true, "jobid": 1, "size": 1234 })
+        );
+        assert_eq!(
+            plan["paths"][1],
+            json!({ "path": "/etc/nginx/", "found": true })
+        );
+        assert_eq!(plan["volumes"][0]["volumename"], "Full-0001");
+        assert!(plan["volumes"][0].get("problem").is_none());
+        assert_eq!(plan["volumes"][1]["jobids"], json!([2]));
+        assert!(plan["volumes"][1]["problem"]
+            .as_str()
+            .unwrap()
+            .starts_with("is purged"));
+        assert_eq!(plan["storages"], json!(["File"]));
+        // A directory makes the estimate the size of the whole chain
+        assert_eq!(plan["estimate"], "jobs");
+        assert_eq!(plan["estimated_files"], 12);
+        assert_eq!(plan["estimated_bytes"], 1100);
+        assert_eq!(
+            plan["warnings"],
+            json!(["Volume Incr-0002 is purged: its jobs are no longer in the catalog"])
+        );
+    }
+
+    #[tokio::test]
+    async fn warns_about_other_filesets_and_missing_chains() {
+        let fake = FakeBconsole::new(CATALOG);
+        let plan = plan(
+            &fake,
+            json!({ "client": "web01-fd", "paths": "/etc/hosts" }),
+        )
+        .await;
+
+        assert_eq!(plan["fileset"], "Catalog");
+        assert_eq!(plan["jobs"][0]["jobid"], 3);
+        assert_eq!(
+            plan["paths"][0],
+            json!({ "path": "/etc/hosts", "found": false })
+        );
+        let warnings: Vec<&str> = plan["warnings"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|warning| warning.as_str().unwrap())
+            .collect();
+        assert_eq!(warnings.len(), 4, "{:?}", warnings);
+        assert!(warnings[0].contains("also has backups of FileSet LinuxAll"));
+        assert!(warnings[1].contains("found no backup chain for job 3"));
+        assert_eq!(warnings[2], "/etc/hosts is not in the backups");
+        assert_eq!(warnings[3], "Job 3 has no volumes in the catalog");
+    }
+}
@@ -0,0 +1,969 @@
+use anyhow::Result;
+use md5::{Digest, Md5};
+use serde_json::{json, Value};
+use tracing::warn;
+
+use super::browse::backup_as_of;
+use super::ToolOutput;
+use crate::bareos::BareosClient;
+use crate::confirm::{Confirmations, Holder};
+use crate::validate::{quote, Arguments, InvalidParams};
+
+const JOB_LEVELS: &[&str] = &["Full", "Incremental", "Differential", "VirtualFull"];
+const VOLUME_STATUSES: &[&str] = &[
+    "Append",
+    "Archive",
+    "Disabled",
+    "Full",
+    "Used",
+    "Cleaning",
+    "Recycle",
+    "Read-Only",
+    "Error",
+];
+
+const REPLACE_OPTIONS: &[&str] = &["always", "ifnewer", "ifolder", "never"];
+const DEFAULT_WHERE: &str = "/tmp/bareos-restores";
+
+const TOOLS: &[&str] = &[
+    "run_job",
+    "rerun_job",
+    "cancel_job",
+    "enable_job",
+    "disable_job",
+    "mount",
+    "unmount",
+    "update_volume_status",
+    "run_restore",
+];
+
+pub fn is_write_tool(name: &str) -> bool {
+    TOOLS.contains(&name)
+}
+
+fn dry_run_property() -> Value {
+    json!({
+        "type": "boolean",
+        "description": "Only return the bconsole command that would be sent, without executing it."
+    })
+}
+
+fn confirmation_token_property() -> Value {
+    json!({
+        "type": "string",
+        "description": "Token from the preview returned by a first call with the same arguments. Without it the tool only previews the change."
+    })
+}
+
+fn annotations(title: &str, destructive: bool, idempotent: bool) -> Value {
+    json!({
+        "title": title,
+        "readOnlyHint": false,
+        "destructiveHint": destructive,
+        "idempotentHint": idempotent,
+        "openWorldHint": false
+    })
+}
+
+/// Mutating tools; only advertised when the client runs in write mode.
+pub fn definitions() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "run_job",
+            "description": "Start a configured Bareos job now. Optional overrides replace the job's configured level, client, pool, storage or fileset. Returns the new job ID.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "job": {
+                        "type": "string",
+                        "description": "Name of the job resource to run"
+                    },
+                    "level": {
+                        "type": "string",
+                        "enum": JOB_LEVELS,
+                        "description": "Backup level override"
+                    },
+                    "client": {
+                        "type": "string",
+                        "description": "Client override"
+                    },
+                    "pool": {
+                        "type": "string",
+                        "description": "Pool override"
+                    },
+                    "storage": {
+                        "type": "string",
+                        "description": "Storage override"
+                    },
+                    "fileset": {
+                        "type": "string",
+                        "description": "FileSet override"
+                    },
+                    "dry_run": dry_run_property(),
+                    "confirmation_token": confirmation_token_property()
+                },
+                "required": ["job"]
+            },
+            "annotations": annotations("Run job", false, false)
+        }),
+        json!({
+            "name": "rerun_job",
+            "description": "Run a previous job again with the same job resource, level, client, fileset, pool and storage, e.g. to retry a failed backup. Returns the new job ID.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "job_id": {
+                        "type": "string",
+                        "description": "The job ID to rerun"
+                    },
+                    "dry_run": dry_run_property(),
+                    "confirmation_token": confirmation_token_property()
+                },
+                "required": ["job_id"]
+            },
+            "annotations": annotations("Rerun job", false, false)
+        }),
+        json!({
+            "name": "cancel_job",
+            "description": "Cancel a running or waiting job.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "job_id": {
+                        "type": "string",
+                        "description": "The job ID to cancel"
+                    },
+                    "dry_run": dry_run_property(),
+                    "confirmation_token": confirmation_token_property()
+                },
+                "required": ["job_id"]
+            },
+            "annotations": annotations("Cancel job", true, true)
+        }),
+        json!({
+            "name": "enable_job",
+            "description": "Enable a job resource so its schedule runs it again. Lasts until the Director is restarted or reloaded.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "job": {
+                        "type": "string",
+                        "description": "Name of the job resource to enable"
+                    },
+                    "dry_run": dry_run_property(),
+                    "confirmation_token": confirmation_token_property()
+                },
+                "required": ["job"]
+            },
+            "annotations": annotations("Enable job", false, true)
+        }),
+        json!({
+            "name": "disable_job",
+            "description": "Disable a job resource so its schedule no longer runs it. Lasts until the Director is restarted or reloaded.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "job": {
+                        "type": "string",
+                        "description": "Name of the job resource to disable"
+                    },
+                    "dry_run": dry_run_property(),
+                    "confirmation_token": confirmation_token_property()
+                },
+                "required": ["job"]
+            },
+            "annotations": annotations("Disable job", true, true)
+        }),
+        json!({
+            "name": "mount",
+            "description": "Mount the volume in a storage device, optionally loading it from an autochanger slot first.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "storage": {
+                        "type": "string",
+                        "description": "Name of the storage resource"
+                    },
+                    "slot": {
+                        "type": "number",
+                        "description": "Autochanger slot to load"
+                    },
+                    "drive": {
+                        "type": "number",
+                        "description": "Drive number of the autochanger"
+                    },
+                    "dry_run": dry_run_property(),
+                    "confirmation_token": confirmation_token_property()
+                },
+                "required": ["storage"]
+            },
+            "annotations": annotations("Mount volume", false, true)
+        }),
+        json!({
+            "name": "unmount",
+            "description": "Unmount the volume in a storage device. Jobs needing the device will wait until it is mounted again.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "storage": {
+                        "type": "string",
+                        "description": "Name of the storage resource"
+                    },
+                    "drive": {
+                        "type": "number",
+                        "description": "Drive number of the autochanger"
+                    },
+                    "dry_run": dry_run_property(),
+                    "confirmation_token": confirmation_token_property()
+                },
+                "required": ["storage"]
+            },
+            "annotations": annotations("Unmount volume", true, true)
+        }),
+        json!({
+            "name": "update_volume_status",
+            "description": "Change the status of a volume in the catalog, e.g. mark it Used so no more jobs are appended, or Read-Only.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "volume": {
+                        "type": "string",
+                        "description": "Name of the volume"
+                    },
+                    "status": {
+                        "type": "string",
+                        "enum": VOLUME_STATUSES,
+                        "description": "New volume status"
+                    },
+                    "dry_run": dry_run_property(),
+                    "confirmation_token": confirmation_token_property()
+                },
+                "required": ["volume", "status"]
+            },
+            "annotations": annotations("Update volume status", true, true)
+        }),
+        json!({
+            "name": "run_restore",
+            "description": "Start a restore of a client's files and return the new job ID. Select either paths with 'files' (restored as of 'date', or from the latest backup) or entries listed by browse_backup with 'jobids' plus 'fileids' and/or 'dirids' (their pathid). Files go to 'where' (default /tmp/bareos-restores); restoring over the original files with where='/' is refused unless in_place is true. Run plan_restore first to check that the volumes are available.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "client": {
+                        "type": "string",
+                        "description": "The client whose backups to restore from"
+                    },
+                    "files": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Files or directories (ending in '/') to restore"
+                    },
+                    "date": {
+                        "type": "string",
+                        "description": "With 'files': restore the state as of the end of this day (YYYY-MM-DD) instead of the latest backup"
+                    },
+                    "jobids": {
+                        "type": "array",
+                        "items": { "type": "integer" },
+                        "description": "BVFS selection: the jobids returned by browse_backup"
+                    },
+                    "fileids": {
+                        "type": "array",
+                        "items": { "type": "integer" },
+                        "description": "BVFS selection: fileid of files listed by browse_backup"
+                    },
+                    "dirids": {
+                        "type": "array",
+                        "items": { "type": "integer" },
+                        "description": "BVFS selection: pathid of directories listed by browse_backup, restored with everything below them"
+                    },
+                    "where": {
+                        "type": "string",
+                        "description": "Directory on the restore client to restore into (default /tmp/bareos-restores); '/' restores in place"
+                    },
+                    "replace": {
+                        "type": "string",
+                        "enum": REPLACE_OPTIONS,
+                        "description": "What to do with files that already exist (default never)"
+                    },
+                    "in_place": {
+                        "type": "boolean",
+                        "description": "Must be true to restore with where='/', over the files on the client"
+                    },
+                    "restore_client": {
+                        "type": "string",
+                        "description": "Restore to this client instead of the one backed up"
+                    },
+                    "restore_job": {
+                        "type": "string",
+                        "description": "Restore job resource to use when the Director has more than one"
+                    },
+                    "fileset": {
+                        "type": "string",
+                        "description": "With 'files': the FileSet of the backups, when the client has more than one"
+                    },
+                    "dry_run": dry_run_property(),
+                    "confirmation_token": confirmation_token_property()
+                },
+                "required": ["client"]
+            },
+            "annotations": annotations("Restore files", true, false)
+        }),
+    ]
+}
+
+fn join_ids(ids: &[u64]) -> String {
+    ids.iter().map(u64::to_string).collect::<Vec<_>>().join(",")
+}
+
+/// A BVFS restore selection: the name of its catalog table and the `.bvfs_restore` command
+/// that fills it.
+fn bvfs_selection(arguments: &Arguments) -> Result<Option<(String, String)>> {
+    let job_ids = arguments.optional_ids("jobids")?;
+    let file_ids = arguments.optional_ids("fileids")?.unwrap_or_default();
+    let dir_ids = arguments.optional_ids("dirids")?.unwrap_or_default();
+    let Some(job_ids) = job_ids else {
+        if file_ids.is_empty() && dir_ids.is_empty() {
+            return Ok(None);
+        }
+        return Err(InvalidParams {
+            argument: "jobids".to_string(),
+            reason: "is required with fileids or dirids".to_string(),
+        }
+        .into());
+    };
+    if job_ids.is_empty() || (file_ids.is_empty() && dir_ids.is_empty()) {
+        return Err(InvalidParams {
+            argument: "fileids".to_string(),
+            reason: "jobids and at least one fileid or dirid must be given".to_string(),
+        }
+        .into());
+    }
+
+    let mut selection = format!("jobid={}", join_ids(&job_ids));
+    if !file_ids.is_empty() {
+        selection.push_str(&format!(" fileid={}", join_ids(&file_ids)));
+    }
+    if !dir_ids.is_empty() {
+        selection.push_str(&format!(" dirid={}", join_ids(&dir_ids)));
+    }
+    // Named after the selection, so the preview and the confirmed call build the same
+    // commands and the confirmation token covers the selection too. The Director only takes
+    // `b2` followed by digits as a restore table name.
+    let digest = Md5::digest(selection.as_bytes());
+    let table = format!(
+        "b2{}",
+        u64::from_be_bytes(digest[..8].try_into().expect("MD5 digests are 16 bytes"))
+    );
+    let command = format!(".bvfs_restore path={} {}", table, selection);
+    Ok(Some((table, command)))
+}
+
+// A table of its own for one confirmed restore, so concurrent restores of the same selection
+// do not drop each other's table
+fn bvfs_call_table() -> Result<String> {
+    let mut bytes = [0u8; 8];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to generate a BVFS table name: {}", e))?;
+    Ok(format!("b2{}", u64::from_be_bytes(bytes)))
+}
+
+// `.bvfs_restore` answers "OK" once the table is filled and prints the error otherwise
+fn check_bvfs_restore(output: &str) -> Result<()> {
+    if output.lines().any(|line| line.trim() == "OK") {
+        return Ok(());
+    }
+    let reason = output.trim();
+    anyhow::bail!(
+        "Failed to build the BVFS restore selection: {}",
+        if reason.is_empty() {
+            "the Director did not confirm it"
+        } else {
+            reason
+        }
+    )
+}
+
+// Drops `.` and empty components, so `/.`, `//` and `/./` are all recognized as `/`
+fn normalize_target(target: &str) -> Result<String, InvalidParams> {
+    let mut components = Vec::new();
+    for component in target.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                return Err(InvalidParams {
+                    argument: "where".to_string(),
+                    reason: "must not contain '..'".to_string(),
+                })
+            }
+            component => components.push(component),
+        }
+    }
+    if components.is_empty() || target.starts_with('/') {
+        Ok(format!("/{}", components.join("/")))
+    } else {
+        Ok(components.join("/"))
+    }
+}
+
+fn restore_target(arguments: &Arguments) -> Result<String> {
+    let target = normalize_target(
+        &arguments
+            .optional_path("where")?
+            .unwrap_or_else(|| DEFAULT_WHERE.to_string()),
+    )?;
+    if target == "/" && !arguments.flag("in_place")? {
+        return Err(InvalidParams {
+            argument: "where".to_string(),
+            reason: "'/' restores over the files on the client; set in_place=true to do that"
+                .to_string(),
+        }
+        .into());
+    }
+    Ok(target)
+}
+
+fn restore_command(arguments: &Arguments) -> Result<String> {
+    let mut cmd = format!("restore client={}", quote(&arguments.name("client")?));
+    for (key, argument) in [
+        ("restoreclient", "restore_client"),
+        ("restorejob", "restore_job"),
+    ] {
+        if let Some(value) = arguments.optional_name(argument)? {
+            cmd.push_str(&format!(" {}={}", key, quote(&value)));
+        }
+    }
+    cmd.push_str(&format!(
+        " where=\"{}\" replace={}",
+        restore_target(arguments)?,
+        arguments
+            .optional_choice("replace", REPLACE_OPTIONS)?
+            .as_deref()
+            .unwrap_or("never")
+    ));
+
+    match bvfs_selection(arguments)? {
+        Some((table, _)) => {
+            if arguments.has("files") || arguments.has("date") {
+                return Err(InvalidParams {
+                    argument: "files".to_string(),
+                    reason: "cannot be combined with a BVFS selection".to_string(),
+                }
+                .into());
+            }
+            cmd.push_str(&format!(" file=?{}", table));
+        }
+        None => {
+            if let Some(fileset) = arguments.optional_name("fileset")? {
+                cmd.push_str(&format!(" fileset={}", quote(&fileset)));
+            }
+            match arguments.optional_date("date")? {
+                Some(date) => cmd.push_str(&format!(" before=\"{} 23:59:59\"", date)),
+                None => cmd.push_str(" current"),
+            }
+            for path in arguments.paths("files")? {
+                let key = if path.ends_with('/') {
+                    "directory"
+                } else {
+                    "file"
+                };
+                cmd.push_str(&format!(" {}=\"{}\"", key, path));
+            }
+        }
+    }
+    cmd.push_str(" yes");
+    Ok(cmd)
+}
+
+/// Builds the exact bconsole command a write tool sends.
+pub fn command(name: &str, arguments: &Arguments) -> Result<String> {
+    let command = match name {
+        "run_job" => {
+            let mut cmd = format!("run job={}", quote(&arguments.name("job")?));
+            if let Some(level) = arguments.optional_choice("level", JOB_LEVELS)? {
+                cmd.push_str(&format!(" level={}", level));
+            }
+            for key in ["client", "pool", "storage", "fileset"] {
+                if let Some(value) = arguments.optional_name(key)? {
+                    cmd.push_str(&format!(" {}={}", key, quote(&value)));
+                }
+            }
+            cmd.push_str(" yes");
+            cmd
+        }
+        "rerun_job" => format!("rerun jobid={} yes", arguments.job_id("job_id")?),
+        "cancel_job" => format!("cancel jobid={} yes", arguments.job_id("job_id")?),
+        "enable_job" => format!("enable job={}", quote(&arguments.name("job")?)),
+        "disable_job" => format!("disable job={}", quote(&arguments.name("job")?)),
+        "mount" | "unmount" => {
+            let mut cmd = format!("{} storage={}", name, quote(&arguments.name("storage")?));
+            if name == "mount" {
+                if let Some(slot) = arguments.optional_u32("slot")? {
+                    cmd.push_str(&format!(" slot={}", slot));
+                }
+            }
+            if let Some(drive) = arguments.optional_u32("drive")? {
+                cmd.push_str(&format!(" drive={}", drive));
+            }
+            cmd
+        }
+        "update_volume_status" => format!(
+            "update volume={} volstatus={}",
+            quote(&arguments.name("volume")?),
+            arguments.choice("status", VOLUME_STATUSES)?
+        ),
+        "run_restore" => restore_command(arguments)?,
+        _ => anyhow::bail!("Unknown tool: {}", name),
+    };
+    Ok(command)
+}
+
+// bconsole reports new jobs as "Job queued. JobId=1234"
+fn queued_job_id(output: &str) -> Option<u64> {
+    let (_, rest) = output.split_once("JobId=")?;
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+// Resolves what a write tool would act on and describes the effect of running it
+async fn preview(
+    client: &BareosClient,
+    name: &str,
+    arguments: &Arguments<'_>,
+) -> Result<(Value, String)> {
+    match name {
+        "run_job" | "enable_job" | "disable_job" => {
+            let job = arguments.name("job")?;
+            if !client.job_names().await?.contains(&job) {
+                anyhow::bail!("No job resource named '{}'", job);
+            }
+            let impact = match name {
+                "run_job" => match arguments.optional_choice("level", JOB_LEVELS)? {
+                    Some(level) => format!("Queues a {} run of job '{}' immediately", level, job),
+                    None => format!(
+                        "Queues a run of job '{}' immediately at its configured level",
+                        job
+                    ),
+                },
+                "enable_job" => format!(
+                    "Job '{}' is started by its schedule again until the Director is reloaded",
+                    job
+                ),
+                _ => format!(
+                    "Job '{}' is no longer started by its schedule until it is enabled again or the Director is reloaded",
+                    job
+                ),
+            };
+            Ok((json!({ "job": job }), impact))
+        }
+        "rerun_job" | "cancel_job" => {
+            let job_id = arguments.job_id("job_id")?;
+            let job = client
+                .get_job_status(job_id)
+                .await?
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("Job {} not found in the catalog", job_id))?;
+            let impact = if name == "rerun_job" {
+                format!(
+                    "Queues a new run of job '{}' with the level, client, fileset, pool and storage of job {}",
+                    job.name, job_id
+                )
+            } else if job.status.is_final() {
+                format!(
+                    "Job {} has already finished; cancelling it has no effect",
+                    job_id
+                )
+            } else {
+                format!(
+                    "Job {} ('{}') is stopped and ends as canceled; data it has not written yet is lost",
+                    job_id, job.name
+                )
+            };
+            Ok((serde_json::to_value(&job)?, impact))
+        }
+        "mount" | "unmount" => {
+            let storage = arguments.name("storage")?;
+            if !client.storage_names().await?.contains(&storage) {
+                anyhow::bail!("No storage resource named '{}'", storage);
+            }
+            let impact = if name == "mount" {
+                format!(
+                    "The Storage Daemon opens the device of storage '{}' so waiting jobs can use it",
+                    storage
+                )
+            } else {
+                format!(
+                    "The device of storage '{}' is released; jobs needing it wait until it is mounted again",
+                    storage
+                )
+            };
+            Ok((json!({ "storage": storage }), impact))
+        }
+        "update_volume_status" => {
+            let volume = arguments.name("volume")?;
+            let status = arguments.choice("status", VOLUME_STATUSES)?;
+            let record = client
+                .list_volumes(None)
+                .await?
+                .into_iter()
+                .find(|record| record.name == volume)
+                .ok_or_else(|| anyhow::anyhow!("Volume '{}' not found in the catalog", volume))?;
+            let mut impact = format!(
+                "Volume '{}' changes status from {} to {}",
+                volume, record.status, status
+            );
+            if status == "Recycle" {
+                impact.push_str("; the data on it will be overwritten when it is reused");
+            }
+            Ok((serde_json::to_value(&record)?, impact))
+        }
+        "run_restore" => {
+            let name = arguments.name("client")?;
+            let clients = client.client_names().await?;
+            let restore_client = arguments
+                .optional_name("restore_client")?
+                .unwrap_or_else(|| name.clone());
+            for client_name in [&name, &restore_client] {
+                if !clients.contains(client_name) {
+                    anyhow::bail!("No client resource named '{}'", client_name);
+                }
+            }
+            if let Some(job) = arguments.optional_name("restore_job")? {
+                if !client.job_names().await?.contains(&job) {
+                    anyhow::bail!("No job resource named '{}'", job);
+                }
+            }
+            let target = restore_target(arguments)?;
+            let replace = arguments
+                .optional_choice("replace", REPLACE_OPTIONS)?
+                .unwrap_or_else(|| "never".to_string());
+
+            let mut preview = json!({
+                "client": name,
+                "restore_client": restore_client,
+                "where": target,
+                "replace": replace
+            });
+            let what = if bvfs_selection(arguments)?.is_some() {
+                let job_ids = arguments.optional_ids("jobids")?.unwrap_or_default();
+                // The selection must not reach into another client's backups
+                for job_id in &job_ids {
+                    let job = client
+                        .get_job_status(*job_id)
+                        .await?
+                        .into_iter()
+                        .next()
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("Job {} not found in the catalog", job_id)
+                        })?;
+                    if job.client.as_deref() != Some(name.as_str()) {
+                        anyhow::bail!("Job {} is not a backup of client '{}'", job_id, name);
+                    }
+                }
+                let files = arguments.optional_ids("fileids")?.unwrap_or_default().len();
+                let directories = arguments.optional_ids("dirids")?.unwrap_or_default().len();
+                preview["jobids"] = json!(job_ids);
+                format!(
+                    "{} files and {} directories selected from jobs {}",
+                    files,
+                    directories,
+                    join_ids(&job_ids)
+                )
+            } else {
+                let date = arguments.optional_date("date")?;
+                let fileset = arguments.optional_name("fileset")?;
+                let (job, warnings) =
+                    backup_as_of(client, &name, fileset.as_deref(), date.as_deref()).await?;
+                let files = arguments.paths("files")?;
+                preview["files"] = json!(files);
+                preview["job"] = serde_json::to_value(&job)?;
+                if !warnings.is_empty() {
+                    preview["warnings"] = json!(warnings);
+                }
+                format!(
+                    "{} as of job {} ({})",
+                    files.join(", "),
+                    job.job_id,
+                    job.start_time.as_deref().unwrap_or("unknown time")
+                )
+            };
+
+            let existing = match replace.as_str() {
+                "always" => "overwrites existing files",
+                "ifnewer" => "overwrites existing files that are older than the backup",
+                "ifolder" => "overwrites existing files that are newer than the backup",
+                _ => "keeps existing files",
+            };
+            let mut impact = format!(
+                "Queues a restore of {} from client '{}' into {} on client '{}'; it {}",
+                what, name, target, restore_client, existing
+            );
+            if target.trim_end_matches('/').is_empty() {
+                impact.insert_str(0, "IN PLACE, over the client's files: ");
+            }
+            Ok((preview, impact))
+        }
+        _ => anyhow::bail!("Unknown tool: {}", name),
+    }
+}
+
+pub async fn call(
+    client: &BareosClient,
+    confirmations: &Confirmations,
+    holder: &Holder,
+    name: &str,
+    arguments: &Arguments<'_>,
+) -> Result<ToolOutput> {
+    let command = command(name, arguments)?;
+    // A BVFS restore first fills a selection table, which the restore command reads
+    let selection = match name {
+        "run_restore" => bvfs_selection(arguments)?,
+        _ => None,
+    };
+
+    if arguments.flag("dry_run")? {
+        let mut result = json!({
+            "command": command,
+            "executed": false
+        });
+        if let Some((_, selection)) = &selection {
+            result["selection_command"] = json!(selection);
+        }
+        return Ok(ToolOutput::Structured(result));
+    }
+
+    // First phase: show what would happen and hand out a token for the second call
+    let Some(token) = arguments.optional_str("confirmation_token")? else {
+        let (target, impact) = preview(client, name, arguments).await?;
+        let token = confirmations.issue(holder, name, &command)?;
+        let mut result = json!({
+            "command": command,
+            "executed": false,
+            "target": target,
+            "impact": impact,
+            "confirmation_token": token,
+            "expires_in_secs": confirmations.ttl().as_secs()
+        });
+        if let Some((_, selection)) = &selection {
+            result["selection_command"] = json!(selection);
+        }
+        return Ok(ToolOutput::Structured(result));
+    };
+
+    confirmations.redeem(token, holder, name, &command)?;
+    let (command, output) = match &selection {
+        Some((shared, selection)) => {
+            let table = bvfs_call_table()?;
+            let selection =
+                selection.replacen(&format!("path={}", shared), &format!("path={}", table), 1);
+            let command =
+                command.replacen(&format!("file=?{}", shared), &format!("file=?{}", table), 1);
+            let output = match client
+                .execute_mutating(&selection)
+                .await
+                .and_then(|output| check_bvfs_restore(&output))
+            {
+                Ok(()) => client.execute_mutating(&command).await,
+                Err(e) => Err(e),
+            };
+            // The job has read the selection by the time it is queued
+            if let Err(e) = client
+                .execute_mutating(&format!(".bvfs_cleanup path={}", table))
+                .await
+            {
+                warn!("Failed to drop BVFS selection {}: {}", table, e);
+            }
+            (command, output?)
+        }
+        None => {
+            let output = client.execute_mutating(&command).await?;
+            (command, output)
+        }
+    };
+    let mut result = json!({
+        "command": command,
+        "executed": true,
+        "output": output
+    });
+    if matches!(name, "run_job" | "rerun_job" | "run_restore") {
+        result["job_id"] = json!(queued_job_id(&output));
+    }
+    Ok(ToolOutput::Structured(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBconsole;
+
+    fn target(arguments: Value) -> Result<String> {
+        restore_target(&Arguments::new(&arguments).unwrap())
+    }
+
+    fn restore(arguments: Value) -> Result<String> {
+        restore_command(&Arguments::new(&arguments).unwrap())
+    }
+
+    #[test]
+    fn restores_to_a_separate_directory_by_default() {
+        assert_eq!(target(json!({})).unwrap(), DEFAULT_WHERE);
+        assert_eq!(
+            target(json!({ "where": "/srv/restore/./web//" })).unwrap(),
+            "/srv/restore/web"
+        );
+        assert!(target(json!({ "where": "/srv/../etc" })).is_err());
+    }
+
+    #[test]
+    fn refuses_in_place_restores_without_the_flag() {
+        for root in ["/", "//", "/.", "/./", "//.", "/././/", "."] {
+            let error = target(json!({ "where": root })).unwrap_err();
+            assert!(error.downcast_ref::<InvalidParams>().is_some(), "{}", root);
+            assert_eq!(
+                target(json!({ "where": root, "in_place": true })).unwrap(),
+                "/"
+            );
+        }
+    }
+
+    #[test]
+    fn builds_restore_commands() {
+        assert_eq!(
+            restore(json!({
+                "client": "web01-fd",
+                "files": ["/etc/nginx/", "/etc/hosts"],
+                "date": "2024-03-01"
+            }))
+            .unwrap(),
+            "restore client=web01-fd where=\"/tmp/bareos-restores\" replace=never \
+             before=\"2024-03-01 23:59:59\" directory=\"/etc/nginx/\" file=\"/etc/hosts\" yes"
+        );
+        assert_eq!(
+            restore(json!({
+                "client": "web01-fd",
+                "restore_client": "web02-fd",
+                "fileset": "LinuxAll",
+                "files": "/etc/hosts",
+                "where": "/",
+                "in_place": true,
+                "replace": "always"
+            }))
+            .unwrap(),
+            "restore client=web01-fd restoreclient=web02-fd where=\"/\" replace=always \
+             fileset=LinuxAll current file=\"/etc/hosts\" yes"
+        );
+        assert!(
+            restore(json!({ "client": "web01-fd", "files": "/etc/hosts", "where": "/." })).is_err()
+        );
+    }
+
+    #[test]
+    fn names_bvfs_selections_after_their_ids() {
+        let arguments = json!({ "jobids": [3, 1], "fileids": [10], "dirids": ["7"] });
+        let (table, command) = bvfs_selection(&Arguments::new(&arguments).unwrap())
+            .unwrap()
+            .unwrap();
+        assert!(table.len() > 2 && table[..2] == *"b2");
+        assert!(table[2..].chars().all(|c| c.is_ascii_digit()));
+        assert_eq!(
+            command,
+            format!(".bvfs_restore path={} jobid=3,1 fileid=10 dirid=7", table)
+        );
+        let restore_cmd = restore(json!({
+            "client": "web01-fd",
+            "jobids": [3, 1],
+            "fileids": [10],
+            "dirids": ["7"]
+        }))
+        .unwrap();
+        assert!(restore_cmd.ends_with(&format!(" file=?{} yes", table)));
+
+        assert!(bvfs_selection(&Arguments::new(&json!({})).unwrap())
+            .unwrap()
+            .is_none());
+        assert!(bvfs_selection(&Arguments::new(&json!({ "fileids": [10] })).unwrap()).is_err());
+        assert!(restore(json!({
+            "client": "web01-fd",
+            "jobids": [3],
+            "fileids": [10],
+            "files": ["/etc/hosts"]
+        }))
+        .is_err());
+    }
+
+    async fn confirmed_restore(fake: &FakeBconsole, arguments: &Value) -> Result<ToolOutput> {
+        let client = fake.client().with_write_mode(true);
+        let confirmations = Confirmations::default();
+        let holder = Holder::new("session-1", "ops");
+        let mut arguments = arguments.clone();
+        let command = command("run_restore", &Arguments::new(&arguments).unwrap())?;
+        arguments["confirmation_token"] =
+            json!(confirmations.issue(&holder, "run_restore", &command)?);
+        call(
+            &client,
+            &confirmations,
+            &holder,
+            "run_restore",
+            &Arguments::new(&arguments).unwrap(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn restores_bvfs_selections_through_a_table_per_call() {
+        let fake = FakeBconsole::new(
+            r#"
+  ".bvfs_restore path="*) echo OK ;;
+  "restore "*) echo "Job queued. JobId=42" ;;"#,
+        );
+        let arguments = json!({ "client": "web01-fd", "jobids": [3], "fileids": [10] });
+        let (shared, _) = bvfs_selection(&Arguments::new(&arguments).unwrap())
+            .unwrap()
+            .unwrap();
+        for _ in 0..2 {
+            let ToolOutput::Structured(result) =
+                confirmed_restore(&fake, &arguments).await.unwrap()
+            else {
+                panic!("expected a structured result");
+            };
+            assert_eq!(result["job_id"], 42);
+        }
+
+        let commands = fake.commands();
+        assert_eq!(commands.len(), 6);
+        let tables: Vec<&str> = commands
+            .iter()
+            .filter_map(|command| command.strip_prefix(".bvfs_cleanup path="))
+            .collect();
+        assert_eq!(tables.len(), 2);
+        assert_ne!(tables[0], tables[1]);
+        for (i, table) in tables.iter().enumerate() {
+            assert_ne!(*table, shared);
+            assert!(table[2..].chars().all(|c| c.is_ascii_digit()));
+            assert_eq!(
+                commands[i * 3],
+                format!(".bvfs_restore path={} jobid=3 fileid=10", table)
+            );
+            assert!(commands[i * 3 + 1].ends_with(&format!(" file=?{} yes", table)));
+        }
+    }
+
+    #[tokio::test]
+    async fn does_not_restore_when_the_selection_fails() {
+        let fake = FakeBconsole::new(
+            r#"
+  ".bvfs_restore path="*) echo "Cannot create restore list." ;;
+  "restore "*) echo "Job queued. JobId=42" ;;"#,
+        );
+        let arguments = json!({ "client": "web01-fd", "jobids": [3], "fileids": [10] });
+        let Err(error) = confirmed_restore(&fake, &arguments).await else {
+            panic!("the restore ran without a selection");
+        };
+        assert!(error.to_string().contains("Cannot create restore list"));
+
+        let commands = fake.commands();
+        assert_eq!(commands.len(), 2);
+        assert!(commands[0].starts_with(".bvfs_restore "));
+        assert!(commands[1].starts_with(".bvfs_cleanup "));
+    }
+}
@@ -0,0 +1,446 @@
+use serde_json::Value;
+use std::fmt;
+
+use crate::model::{JobLevel, JobStatus, JobType};
+
+// Bareos resource names are limited to MAX_NAME_LENGTH (128) including the terminator
+const MAX_NAME_LENGTH: usize = 127;
+const MAX_PATH_LENGTH: usize = 4096;
+
+/// A tool argument that does not match its grammar; reported as JSON-RPC `-32602`.
+#[derive(Debug)]
+pub struct InvalidParams {
+    pub argument: String,
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid argument '{}': {}", self.argument, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidParams {}
+
+fn invalid(argument: &str, reason: impl Into<String>) -> InvalidParams {
+    InvalidParams {
+        argument: argument.to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Typed access to the `arguments` object of a `tools/call` request.
+pub struct Arguments<'a>(&'a Value);
+
+impl<'a> Arguments<'a> {
+    pub fn new(arguments: &'a Value) -> Result<Self, InvalidParams> {
+        match arguments {
+            Value::Object(_) | Value::Null => Ok(Self(arguments)),
+            _ => Err(invalid("arguments", "expected an object")),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&'a Value> {
+        self.0.get(name).filter(|value| !value.is_null())
+    }
+
+    /// Whether the argument was given (and is not null).
+    pub fn has(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn optional_str(&self, name: &str) -> Result<Option<&'a str>, InvalidParams> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value)),
+            Some(_) => Err(invalid(name, "expected a string")),
+        }
+    }
+
+    pub fn job_id(&self, name: &str) -> Result<u64, InvalidParams> {
+        self.optional_job_id(name)?
+            .ok_or_else(|| invalid(name, "is required"))
+    }
+
+    pub fn optional_job_id(&self, name: &str) -> Result<Option<u64>, InvalidParams> {
+        let id = match self.get(name) {
+            None => return Ok(None),
+            Some(Value::Number(number)) => number.as_u64(),
+            Some(Value::String(text)) if text.bytes().all(|b| b.is_ascii_digit()) => {
+                text.parse().ok()
+            }
+            Some(_) => None,
+        };
+        match id {
+            Some(id) if id > 0 => Ok(Some(id)),
+            _ => Err(invalid(name, "must be a positive numeric job ID")),
+        }
+    }
+
+    /// A list of catalog IDs (job, file or path IDs), given as numbers or numeric strings.
+    pub fn optional_ids(&self, name: &str) -> Result<Option<Vec<u64>>, InvalidParams> {
+        let values = match self.get(name) {
+            None => return Ok(None),
+            Some(Value::Array(values)) => values,
+            Some(_) => return Err(invalid(name, "expected an array of IDs")),
+        };
+        values
+            .iter()
+            .map(|value| match value {
+                Value::Number(number) => number.as_u64(),
+                Value::String(text) if text.bytes().all(|b| b.is_ascii_digit()) => {
+                    text.parse().ok()
+                }
+                _ => None,
+            })
+            .map(|id| id.filter(|id| *id > 0))
+            .collect::<Option<Vec<u64>>>()
+            .map(Some)
+            .ok_or_else(|| invalid(name, "must contain positive numeric IDs"))
+    }
+
+    pub fn name(&self, name: &str) -> Result<String, InvalidParams> {
+        self.optional_name(name)?
+            .ok_or_else(|| invalid(name, "is required"))
+    }
+
+    pub fn optional_name(&self, name: &str) -> Result<Option<String>, InvalidParams> {
+        match self.optional_str(name)? {
+            None => Ok(None),
+            Some(value) => resource_name(name, value).map(Some),
+        }
+    }
+
+    pub fn optional_job_status(&self, name: &str) -> Result<Option<String>, InvalidParams> {
+        self.optional_code(name, |code| {
+            !matches!(JobStatus::from_code(code), JobStatus::Other(_))
+        })
+    }
+
+    pub fn optional_job_type(&self, name: &str) -> Result<Option<String>, InvalidParams> {
+        self.optional_code(name, |code| {
+            !matches!(JobType::from_code(code), JobType::Other(_))
+        })
+    }
+
+    pub fn optional_job_level(&self, name: &str) -> Result<Option<String>, InvalidParams> {
+        self.optional_code(name, |code| {
+            code != ' ' && !matches!(JobLevel::from_code(code), JobLevel::Other(_))
+        })
+    }
+
+    fn optional_code(
+        &self,
+        name: &str,
+        known: impl Fn(char) -> bool,
+    ) -> Result<Option<String>, InvalidParams> {
+        let Some(value) = self.optional_str(name)? else {
+            return Ok(None);
+        };
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(code), None) if known(code) => Ok(Some(code.to_string())),
+            _ => Err(invalid(name, "must be a single-letter Bareos code")),
+        }
+    }
+
+    pub fn choice(&self, name: &str, choices: &[&str]) -> Result<String, InvalidParams> {
+        self.optional_choice(name, choices)?
+            .ok_or_else(|| invalid(name, "is required"))
+    }
+
+    pub fn optional_choice(
+        &self,
+        name: &str,
+        choices: &[&str],
+    ) -> Result<Option<String>, InvalidParams> {
+        match self.optional_str(name)? {
+            None => Ok(None),
+            Some(value) if choices.contains(&value) => Ok(Some(value.to_string())),
+            Some(_) => Err(invalid(
+                name,
+                format!("must be one of {}", choices.join(", ")),
+            )),
+        }
+    }
+
+    /// A file or directory path as stored in the catalog. Paths are passed to bconsole in
+    /// double quotes, so quotes and control characters are refused.
+    pub fn optional_path(&self, name: &str) -> Result<Option<String>, InvalidParams> {
+        self.optional_str(name)?
+            .map(|value| path(name, value))
+            .transpose()
+    }
+
+    /// One or more paths, given as an array or as a single string.
+    pub fn paths(&self, name: &str) -> Result<Vec<String>, InvalidParams> {
+        let values = match self.get(name) {
+            None => return Err(invalid(name, "is required")),
+            Some(Value::Array(values)) if values.is_empty() => {
+                return Err(invalid(name, "must not be empty"))
+            }
+            Some(Value::Array(values)) => values.iter().collect(),
+            Some(value) => vec![value],
+        };
+        values
+            .into_iter()
+            .map(|value| match value {
+                Value::String(value) => path(name, value),
+                _ => Err(invalid(name, "expected a string or an array of strings")),
+            })
+            .collect()
+    }
+
+    /// A calendar date in `YYYY-MM-DD` form, as the catalog stores it.
+    pub fn optional_date(&self, name: &str) -> Result<Option<String>, InvalidParams> {
+        let Some(value) = self.optional_str(name)? else {
+            return Ok(None);
+        };
+        let bytes = value.as_bytes();
+        let valid = bytes.len() == 10
+            && bytes.iter().enumerate().all(|(i, b)| match i {
+                4 | 7 => *b == b'-',
+                _ => b.is_ascii_digit(),
+            })
+            && {
+                let field = |range: std::ops::Range<usize>| value[range].parse().unwrap_or(0);
+                let (year, month, day) = (field(0..4), field(5..7), field(8..10));
+                (1..=days_in_month(year, month)).contains(&day)
+            };
+        if valid {
+            Ok(Some(value.to_string()))
+        } else {
+            Err(invalid(name, "must be a date in YYYY-MM-DD form"))
+        }
+    }
+
+    pub fn optional_u32(&self, name: &str) -> Result<Option<u32>, InvalidParams> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(value) => value
+                .as_u64()
+                .and_then(|number| u32::try_from(number).ok())
+                .map(Some)
+                .ok_or_else(|| invalid(name, "must be a non-negative integer")),
+        }
+    }
+
+    pub fn flag(&self, name: &str) -> Result<bool, InvalidParams> {
+        match self.get(name) {
+            None => Ok(false),
+            Some(Value::Bool(flag)) => Ok(*flag),
+            Some(_) => Err(invalid(name, "expected a boolean")),
+        }
+    }
+}
+
+// 0 for months outside 1..=12
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        _ => 0,
+    }
+}
+
+/// Checks a file or directory path: not empty, bounded and free of quotes and control characters.
+pub fn path(name: &str, value: &str) -> Result<String, InvalidParams> {
+    if value.is_empty() {
+        return Err(invalid(name, "must not be empty"));
+    }
+    if value.len() > MAX_PATH_LENGTH {
+        return Err(invalid(
+            name,
+            format!("must be at most {} bytes", MAX_PATH_LENGTH),
+        ));
+    }
+    if let Some(c) = value.chars().find(|c| c.is_control() || *c == '"') {
+        return Err(invalid(name, format!("contains {:?}", c)));
+    }
+    Ok(value.to_string())
+}
+
+/// Checks a Director resource name (job, client, pool, volume, ...) against Bareos' character set.
+pub fn resource_name(argument: &str, value: &str) -> Result<String, InvalidParams> {
+    if value.is_empty() {
+        return Err(invalid(argument, "must not be empty"));
+    }
+    if value.len() > MAX_NAME_LENGTH {
+        return Err(invalid(
+            argument,
+            format!("must be at most {} characters", MAX_NAME_LENGTH),
+        ));
+    }
+    if let Some(c) = value
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | ' ')))
+    {
+        return Err(invalid(
+            argument,
+            format!(
+                "contains {:?}; only letters, digits, space and -_.: are allowed",
+                c
+            ),
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Formats a validated value for a bconsole `key=value` argument.
+pub fn quote(value: &str) -> String {
+    if value.contains(' ') {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn with<T>(value: Value, check: impl FnOnce(&Arguments) -> T) -> T {
+        let arguments = json!({ "value": value });
+        check(&Arguments::new(&arguments).unwrap())
+    }
+
+    #[test]
+    fn checks_resource_names() {
+        assert_eq!(
+            resource_name("job", "backup-web01.daily:1").unwrap(),
+            "backup-web01.daily:1"
+        );
+        assert_eq!(resource_name("job", "backup db").unwrap(), "backup db");
+        for bad in [
+            "",
+            "web\nstatus dir",
+            "web;status",
+            "web\"",
+            "@exec",
+            "web\u{0}",
+        ] {
+            assert!(resource_name("job", bad).is_err(), "{:?}", bad);
+        }
+        assert!(resource_name("job", &"a".repeat(127)).is_ok());
+        assert!(resource_name("job", &"a".repeat(128)).is_err());
+    }
+
+    #[test]
+    fn checks_paths() {
+        for good in ["/etc/nginx/", "/srv/my files/a;b@c", "C:/Users/"] {
+            assert_eq!(path("path", good).unwrap(), good);
+        }
+        for bad in ["", "/etc/\nstatus", "/etc/\"x", "/etc/\r", "/etc/\t"] {
+            assert!(path("path", bad).is_err(), "{:?}", bad);
+        }
+        assert!(path("path", &"/".repeat(4097)).is_err());
+    }
+
+    #[test]
+    fn quotes_names_with_spaces() {
+        assert_eq!(quote("backup-web"), "backup-web");
+        assert_eq!(quote("backup db"), "\"backup db\"");
+    }
+
+    #[test]
+    fn checks_single_letter_codes() {
+        assert_eq!(
+            with(json!("T"), |a| a.optional_job_status("value")).unwrap(),
+            Some("T".to_string())
+        );
+        assert_eq!(
+            with(Value::Null, |a| a.optional_job_type("value")).unwrap(),
+            None
+        );
+        for bad in ["", "TT", "T;", "T\n", "\"", "@", " ", "!"] {
+            assert!(
+                with(json!(bad), |a| a.optional_job_status("value")).is_err(),
+                "{:?}",
+                bad
+            );
+        }
+        assert!(with(json!(" "), |a| a.optional_job_level("value")).is_err());
+        assert!(with(json!(1), |a| a.optional_job_level("value")).is_err());
+    }
+
+    #[test]
+    fn checks_ids() {
+        assert_eq!(
+            with(json!(42), |a| a.optional_job_id("value")).unwrap(),
+            Some(42)
+        );
+        assert_eq!(
+            with(json!("42"), |a| a.optional_job_id("value")).unwrap(),
+            Some(42)
+        );
+        for bad in [
+            json!(0),
+            json!(-1),
+            json!(1.5),
+            json!("4 2"),
+            json!("42;"),
+            json!("42\n"),
+            json!("@42"),
+            json!("\"42\""),
+        ] {
+            assert!(
+                with(bad.clone(), |a| a.optional_job_id("value")).is_err(),
+                "{}",
+                bad
+            );
+        }
+        assert_eq!(
+            with(json!([3, "1"]), |a| a.optional_ids("value")).unwrap(),
+            Some(vec![3, 1])
+        );
+        for bad in [
+            json!("3,1"),
+            json!([3, "1 2"]),
+            json!(["1;2"]),
+            json!([0]),
+            json!(["@1"]),
+        ] {
+            assert!(
+                with(bad.clone(), |a| a.optional_ids("value")).is_err(),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn checks_calendar_dates() {
+        for good in ["2024-02-29", "2023-12-31", "2000-02-29", "2024-04-30"] {
+            assert_eq!(
+                with(json!(good), |a| a.optional_date("value")).unwrap(),
+                Some(good.to_string())
+            );
+        }
+        for bad in [
+            "2024-02-31",
+            "2023-02-29",
+            "1900-02-29",
+            "2024-04-31",
+            "2024-13-01",
+            "2024-00-10",
+            "2024-01-00",
+            "2024-1-01",
+            "2024-01-01 ",
+            "2024-01-01;",
+            "2024-01-0\n",
+            "\"2024-01-01",
+            "@2024-01-1",
+        ] {
+            assert!(
+                with(json!(bad), |a| a.optional_date("value")).is_err(),
+                "{:?}",
+                bad
+            );
+        }
+    }
+}